@@ -10,12 +10,23 @@ pub struct Image {
   pub size: Size,
   pub rotation: Rotation,
   pub quality: Quality,
-  pub format: Format 
+  pub format: Format,
+  pub version: Version
+}
+
+/// The version of the Image API syntax to request with.
+/// 
+/// The default is Version::V2, for 3.0 a full size is sent as max. 
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+  #[default]
+  V2,
+  V3
 }
 
 impl Image {
   /// Creates a new Image API with the host required or optionally the host 
-  /// + prefixes. The host argument requires the full base url with the scheme 
+  /// and prefixes. The host argument requires the full base url with the scheme 
   /// included. 
   ///
   /// ```rust,ignore
@@ -42,6 +53,11 @@ impl Image {
     self.prefixes = prefixes.iter().map(|s| s.to_string()).collect();
   }
 
+  /// Sets the version of the Image API syntax used when building urls
+  pub fn version(&mut self, version: Version) {
+    self.version = version;
+  }
+
   /// Set the image identifier for the next request
   pub fn identifier(&mut self, identifier: &str) {
     self.identifier = identifier.into()
@@ -65,7 +81,7 @@ impl Image {
 
   /// Sets region to be returned is specified as a sequence of percentages of the full image’s dimensions, as reported in the image information document. Thus, x represents the number of pixels from the 0 position on the horizontal axis, calculated as a percentage of the reported width. w represents the width of the region, also calculated as a percentage of the reported width. The same applies to y and h respectively
   pub fn pct_region(&mut self,  x: f32, y: f32, w: f32, h: f32) {
-    self.region = Region::Pct({Percentage{ x, y, w, h }})
+    self.region = Region::Pct(Percentage{ x, y, w, h })
   }
 
   /// Sets the  image or region is returned at the maximum size available, as indicated by maxWidth, maxHeight, maxArea in the profile description.
@@ -81,8 +97,8 @@ impl Image {
   }
 
   /// Sets the image or region should be scaled so that its height is exactly equal to h, and the width will be a calculated value that maintains the aspect ratio of the extracted region.
-  pub fn height(&mut self, w: usize) {
-    self.size = Size::W(w);
+  pub fn height(&mut self, h: usize) {
+    self.size = Size::H(h);
   }
 
   /// Sets the width and height of the returned image is scaled to n% of the width and height of the extracted region. The aspect ratio of the returned image is the same as that of the extracted region.
@@ -100,6 +116,42 @@ impl Image {
     self.size = Size::LtWH(w,h);
   }
 
+  /// Sets the image or region to be returned at the maximum size available, which may be larger than the extracted region. Requires the sizeUpscaling feature and sets the version to 3.0.
+  pub fn upscaled_max_size(&mut self) {
+    self.size = Size::UpMax;
+    self.version = Version::V3;
+  }
+
+  /// Sets the image or region to be scaled so that its width is exactly w, which may be larger than the extracted region. Requires the sizeUpscaling feature and sets the version to 3.0.
+  pub fn upscaled_width(&mut self, w: usize) {
+    self.size = Size::UpW(w);
+    self.version = Version::V3;
+  }
+
+  /// Sets the image or region to be scaled so that its height is exactly h, which may be larger than the extracted region. Requires the sizeUpscaling feature and sets the version to 3.0.
+  pub fn upscaled_height(&mut self, h: usize) {
+    self.size = Size::UpH(h);
+    self.version = Version::V3;
+  }
+
+  /// Sets the width and height of the returned image to n% of the extracted region, where n may be greater than 100. Requires the sizeUpscaling feature and sets the version to 3.0.
+  pub fn upscaled_pct_size(&mut self, n: u16) {
+    self.size = Size::UpPct(n);
+    self.version = Version::V3;
+  }
+
+  /// Sets the width and height of the returned image to exactly w and h, either of which may be larger than the extracted region. Requires the sizeUpscaling feature and sets the version to 3.0.
+  pub fn upscaled_width_height(&mut self, w: usize, h: usize) {
+    self.size = Size::UpWH(w, h);
+    self.version = Version::V3;
+  }
+
+  /// Sets the image content to be scaled for the best fit within w and h, which may be larger than the extracted region. Requires the sizeUpscaling feature and sets the version to 3.0.
+  pub fn upscaled_less_than_width_height(&mut self, w: usize, h: usize) {
+    self.size = Size::UpLtWH(w, h);
+    self.version = Version::V3;
+  }
+
   /// Sets the image to be mirrored
   pub fn mirrored(&mut self) {
     self.rotation = Rotation::Mirror(0.0);
@@ -164,4 +216,17 @@ impl Image {
   pub fn webp(&mut self) {
     self.format = Format::Webp;
   }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn width_and_height_setters() {
+    let mut api = Image::new("https://example.org");
+    api.width(300);
+    assert_eq!(Size::W(300), api.size);
+    api.height(200);
+    assert_eq!(Size::H(200), api.size);
+  }
+}
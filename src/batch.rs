@@ -0,0 +1,170 @@
+//! Downloads many images concurrently over a shared client
+use super::*;
+use crate::requests::fetch_image;
+use futures::stream::{Stream, StreamExt};
+use std::path::PathBuf;
+
+/// Runs image requests concurrently on a shared session, with at most 
+/// `concurrency` requests in flight, yielding each response alongside the 
+/// image that requested it as they complete. Results arrive in completion 
+/// order rather than the order of the input stream.
+///
+/// ```rust,ignore
+/// use iiif::*;
+/// use futures::stream::{self, StreamExt};
+///
+/// let mut batch = BatchDownloader::new(&Client::new());
+/// batch.concurrency(16);
+/// batch.path_template("harvest/{identifier}/{size}.{format}");
+///
+/// let images = stream::iter(identifiers).map(|id| {
+///   let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+///   api.identifier(id);
+///   api
+/// });
+/// let mut results = batch.download(images);
+/// while let Some((image, result)) = results.next().await {
+///   if let Err(e) = result {
+///     eprintln!("{}: {}", image.identifier, e);
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BatchDownloader {
+  pub session: Session,
+  pub concurrency: usize,
+  pub path_template: Option<String>
+}
+
+impl BatchDownloader {
+  /// Creates a downloader on a clone of the client, which shares its 
+  /// connection pool, running 8 requests at a time with the default retry 
+  /// policy and writing nothing to disk
+  pub fn new(client: &Client) -> BatchDownloader {
+    BatchDownloader::with_session(&Session::new(client))
+  }
+
+  /// Creates a downloader that sends its requests through a clone of the session
+  pub fn with_session(session: &Session) -> BatchDownloader {
+    BatchDownloader {
+      session: session.clone(),
+      concurrency: 8,
+      path_template: None
+    }
+  }
+
+  /// Sets the maximum number of requests in flight, at least one request 
+  /// is always allowed
+  pub fn concurrency(&mut self, concurrency: usize) {
+    self.concurrency = concurrency.max(1);
+  }
+
+  /// Writes each successful response to a path built from the template. 
+  /// The placeholders `{identifier}`, `{region}`, `{size}`, `{rotation}`, 
+  /// `{quality}` and `{format}` are replaced with the image's parameters, 
+  /// any path separators and leading dots within a parameter become 
+  /// underscores so it cannot escape the template's directory. Missing 
+  /// directories are created.
+  pub fn path_template(&mut self, template: &str) {
+    self.path_template = Some(template.into());
+  }
+
+  /// The path an image would be written to under the path template
+  pub fn path(&self, image: &Image) -> Option<PathBuf> {
+    self.path_template.as_ref().map(|template| {
+      let params = [
+        ("{identifier}", image.identifier.clone()),
+        ("{region}", image.region.to_string()),
+        ("{size}", image.size_param()),
+        ("{rotation}", image.rotation.to_string()),
+        ("{quality}", image.quality.to_string()),
+        ("{format}", image.format.to_string())
+      ];
+      let path = params.iter().fold(template.clone(), |path, (key, value)| {
+        path.replace(key, &sanitize(value))
+      });
+      PathBuf::from(path)
+    })
+  }
+
+  /// Requests every image in the stream, yielding the originating image 
+  /// with its result as each request completes. When a path template is set
+  /// the image is written to disk before it is yielded and a failed write 
+  /// is returned as `Error::Io`.
+  pub fn download<'a, S>(&'a self, images: S) -> impl Stream<Item = (Image, Result<Response, Error>)> + 'a
+  where S: Stream<Item = Image> + 'a {
+    images
+      .map(move |image| async move {
+        let result = self.download_one(&image).await;
+        (image, result)
+      })
+      .buffer_unordered(self.concurrency.max(1))
+  }
+
+  // Requests the image and writes it out, only reporting it as finished 
+  // once the write has succeeded
+  async fn download_one(&self, image: &Image) -> Result<Response, Error> {
+    let url = image.image_url()?;
+    let result = match fetch_image(&self.session, &url, image.format).await {
+      Ok(response) => self.write(image, &response).await.map(|_| response),
+      Err(e) => Err(e)
+    };
+    self.session.report_result(&url, &result, |response| response.image.len() as u64);
+    result
+  }
+
+  async fn write(&self, image: &Image, response: &Response) -> Result<(), Error> {
+    if let Some(path) = self.path(image) {
+      if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+      }
+      tokio::fs::write(&path, &response.image).await?;
+    }
+    Ok(())
+  }
+}
+
+// Makes a parameter safe to use as a single path segment, replacing path 
+// separators and leading dots so `.` and `..` cannot walk the directory tree
+fn sanitize(value: &str) -> String {
+  let value = value.replace(['/', '\\'], "_");
+  let dots = value.len() - value.trim_start_matches('.').len();
+  format!("{}{}", "_".repeat(dots), &value[dots..])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn renders_path_template() {
+    let mut batch = BatchDownloader::new(&Client::new());
+    assert_eq!(None, batch.path(&Image::new("https://example.org")));
+
+    batch.path_template("out/{identifier}/{region}/{size}.{format}");
+    let mut api = Image::new("https://example.org/iiif");
+    api.identifier("ark:/12025/654");
+    api.width(500);
+    api.png();
+    assert_eq!(Some(PathBuf::from("out/ark:_12025_654/full/500,.png")), batch.path(&api));
+
+    api.version(Version::V3);
+    api.size = Size::Full;
+    assert_eq!(Some(PathBuf::from("out/ark:_12025_654/full/max.png")), batch.path(&api));
+  }
+
+  #[test]
+  fn contains_path_traversal() {
+    let mut batch = BatchDownloader::new(&Client::new());
+    batch.path_template("out/{identifier}.{format}");
+    let mut api = Image::new("https://example.org/iiif");
+    for (identifier, path) in [("..", "out/__.jpg"), ("../../etc/passwd", "out/___.._etc_passwd.jpg"), 
+                               (".hidden", "out/_hidden.jpg"), ("a..b", "out/a..b.jpg")] {
+      api.identifier(identifier);
+      assert_eq!(Some(PathBuf::from(path)), batch.path(&api));
+    }
+    batch.path_template("out/{identifier}/full.jpg");
+    api.identifier(".");
+    assert_eq!(Some(PathBuf::from("out/_/full.jpg")), batch.path(&api));
+  }
+}
@@ -0,0 +1,536 @@
+//! An on-disk HTTP cache for info.json documents and images
+use super::*;
+use bytes::Bytes;
+use reqwest::header::{self, HeaderMap};
+use percent_encoding::percent_decode_str;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Stores successful responses on disk keyed by their url, honouring the 
+/// server's Cache-Control and Expires headers for freshness and revalidating 
+/// stale entries with conditional requests using their ETag or Last-Modified 
+/// validators. Urls are normalised first, so spellings that differ only in 
+/// percent-encoding or a fragment share an entry. The least recently used 
+/// entries are evicted once the bodies exceed the maximum size, recency is 
+/// kept in the files' modified times so it survives reopening the cache. 
+/// Clones share the same entries and counters.
+///
+/// ```rust,ignore
+/// use iiif::*;
+///
+/// // Keep up to 512MB of responses
+/// let cache = DiskCache::open("/var/cache/iiif", 512 * 1024 * 1024)?;
+/// let mut session = Session::new(&Client::new());
+/// session.cache(cache.clone());
+///
+/// let info = api.request_info_with(&session).await?;
+/// println!("{:?}", cache.stats());
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+  pub dir: PathBuf,
+  pub max_size: u64,
+  index: Arc<Mutex<Index>>,
+  counters: Arc<Counters>
+}
+
+/// The number of requests answered by the cache
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+  /// Fresh entries returned without contacting the server
+  pub hits: u64,
+  /// Requests with no usable entry that were fetched in full
+  pub misses: u64,
+  /// Stale entries the server confirmed were unchanged with a 304
+  pub revalidated: u64
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+  hits: AtomicU64,
+  misses: AtomicU64,
+  revalidated: AtomicU64
+}
+
+// The size and recency of each entry keyed by the hash of its url, along 
+// with the keys in order of use so eviction doesn't scan every entry
+#[derive(Debug, Default)]
+struct Index {
+  entries: HashMap<String, (u64, u64)>,
+  recency: BTreeMap<u64, String>,
+  size: u64,
+  clock: u64
+}
+
+/// The stored response headers needed to decide freshness and revalidate
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Metadata {
+  pub url: String,
+  pub status_code: u16,
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
+  /// The end-to-end headers of the original response, other than cookies 
+  /// and credentials, updated by revalidations
+  #[serde(default)]
+  pub headers: Vec<(String, String)>,
+  /// The url after any redirects
+  #[serde(default)]
+  pub final_url: Option<String>,
+  /// Seconds since the unix epoch the entry stays fresh until
+  pub expires: Option<u64>,
+  /// The entry must be revalidated before every use, even while fresh
+  pub no_cache: bool
+}
+
+/// A cached response body along with its metadata
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+  pub metadata: Metadata,
+  pub body: Bytes
+}
+
+impl DiskCache {
+  /// Opens a cache in the directory, creating it if needed and picking up 
+  /// any entries left by a previous run with their file times as recency
+  pub fn open<P: AsRef<Path>>(dir: P, max_size: u64) -> io::Result<DiskCache> {
+    let dir = dir.as_ref().to_path_buf();
+    std::fs::create_dir_all(&dir)?;
+    let mut found = Vec::new();
+    for file in std::fs::read_dir(&dir)? {
+      let path = file?.path();
+      if path.extension().is_some_and(|e| e == "body") {
+        let metadata = std::fs::metadata(&path)?;
+        let key = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        found.push((metadata.modified().unwrap_or(UNIX_EPOCH), key, metadata.len()));
+      }
+    }
+    found.sort();
+    let mut index = Index::default();
+    for (_, key, size) in found {
+      index.insert(key, size);
+    }
+    Ok(DiskCache {
+      dir,
+      max_size,
+      index: Arc::new(Mutex::new(index)),
+      counters: Arc::new(Counters::default())
+    })
+  }
+
+  /// The hit, miss and revalidation counts so far
+  pub fn stats(&self) -> CacheStats {
+    CacheStats {
+      hits: self.counters.hits.load(Ordering::Relaxed),
+      misses: self.counters.misses.load(Ordering::Relaxed),
+      revalidated: self.counters.revalidated.load(Ordering::Relaxed)
+    }
+  }
+
+  /// The total size in bytes of the cached bodies
+  pub fn size(&self) -> u64 {
+    self.lock().size
+  }
+
+  /// Removes every entry from disk, leaving the counters untouched
+  pub async fn clear(&self) -> io::Result<()> {
+    let keys: Vec<String> = self.lock().entries.keys().cloned().collect();
+    for key in keys {
+      self.remove(&key).await?;
+    }
+    Ok(())
+  }
+
+  /// Reads the entry for a url, a missing or unreadable entry is a miss
+  pub(crate) async fn get(&self, url: &Url) -> Option<Entry> {
+    let url = normalize(url);
+    let key = key(&url);
+    if !self.lock().touch(&key) {
+      return None
+    }
+    let metadata = tokio::fs::read(self.path(&key, "meta")).await.ok()?;
+    let metadata: Metadata = serde_json::from_slice(&metadata).ok()?;
+    // Guard against the unlikely case of two urls sharing a hash
+    if metadata.url != url.as_str() {
+      return None
+    }
+    let body = tokio::fs::read(self.path(&key, "body")).await.ok()?;
+    touch_file(self.path(&key, "body")).await;
+    Some(Entry{ metadata, body: body.into() })
+  }
+
+  /// Stores a response if its headers allow it, evicting the least recently 
+  /// used entries to stay under the maximum size
+  pub(crate) async fn put(&self, url: &Url, final_url: &Url, status_code: u16, headers: &HeaderMap, body: &Bytes) -> io::Result<()> {
+    let mut metadata = match Metadata::from_headers(url, status_code, headers, SystemTime::now()) {
+      Some(metadata) if body.len() as u64 <= self.max_size => metadata,
+      _ => return Ok(())
+    };
+    metadata.final_url = Some(final_url.to_string());
+    let key = key(&normalize(url));
+    self.remove(&key).await?;
+    self.write(&key, "body", body).await?;
+    self.write(&key, "meta", &serde_json::to_vec(&metadata)?).await?;
+
+    let evicted = {
+      let mut index = self.lock();
+      index.insert(key, body.len() as u64);
+      let mut evicted = Vec::new();
+      while index.size > self.max_size {
+        match index.pop_oldest() {
+          Some(oldest) => evicted.push(oldest),
+          None => break
+        }
+      }
+      evicted
+    };
+    for key in evicted {
+      remove_files(&self.dir, &key).await?;
+    }
+    Ok(())
+  }
+
+  /// Rewrites an entry's metadata, such as after a revalidation
+  pub(crate) async fn update(&self, url: &Url, metadata: &Metadata) -> io::Result<()> {
+    self.write(&key(&normalize(url)), "meta", &serde_json::to_vec(metadata)?).await
+  }
+
+  /// Removes the entry for a url, such as when a revalidation forbids storing it
+  pub(crate) async fn evict(&self, url: &Url) -> io::Result<()> {
+    self.remove(&key(&normalize(url))).await
+  }
+
+  pub(crate) fn hit(&self) {
+    self.counters.hits.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub(crate) fn miss(&self) {
+    self.counters.misses.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub(crate) fn revalidated(&self) {
+    self.counters.revalidated.fetch_add(1, Ordering::Relaxed);
+  }
+
+  async fn remove(&self, key: &str) -> io::Result<()> {
+    let removed = self.lock().remove(key);
+    match removed {
+      true => remove_files(&self.dir, key).await,
+      false => Ok(())
+    }
+  }
+
+  // Writes through a temporary file with a name unique to this write, so 
+  // concurrent puts of the same url never interleave within a file
+  async fn write(&self, key: &str, extension: &str, contents: &[u8]) -> io::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let unique = format!("{}.{}.tmp", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed));
+    let tmp = self.path(key, &unique);
+    tokio::fs::write(&tmp, contents).await?;
+    match tokio::fs::rename(&tmp, self.path(key, extension)).await {
+      Err(e) => {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        Err(e)
+      }
+      ok => ok
+    }
+  }
+
+  fn path(&self, key: &str, extension: &str) -> PathBuf {
+    self.dir.join(format!("{}.{}", key, extension))
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, Index> {
+    self.index.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+
+impl Index {
+  fn insert(&mut self, key: String, size: u64) {
+    self.remove(&key);
+    self.clock += 1;
+    self.size += size;
+    self.recency.insert(self.clock, key.clone());
+    self.entries.insert(key, (size, self.clock));
+  }
+
+  fn remove(&mut self, key: &str) -> bool {
+    match self.entries.remove(key) {
+      Some((size, used)) => {
+        self.size -= size;
+        self.recency.remove(&used);
+        true
+      }
+      None => false
+    }
+  }
+
+  // Marks an entry as recently used, returning whether it exists
+  fn touch(&mut self, key: &str) -> bool {
+    self.clock += 1;
+    match self.entries.get_mut(key) {
+      Some((_, used)) => {
+        self.recency.remove(used);
+        *used = self.clock;
+        self.recency.insert(self.clock, key.to_string());
+        true
+      }
+      None => false
+    }
+  }
+
+  // Removes the least recently used entry, returning its key
+  fn pop_oldest(&mut self) -> Option<String> {
+    let (_, key) = self.recency.pop_first()?;
+    if let Some((size, _)) = self.entries.remove(&key) {
+      self.size -= size;
+    }
+    Some(key)
+  }
+}
+
+impl Metadata {
+  /// Reads the caching headers, returning None if the response must not 
+  /// be stored or could never be reused
+  pub(crate) fn from_headers(url: &Url, status_code: u16, headers: &HeaderMap, now: SystemTime) -> Option<Metadata> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.trim().to_string());
+    let mut no_cache = false;
+    let mut max_age = None;
+    for directive in header(header::CACHE_CONTROL).unwrap_or_default().split(',') {
+      let directive = directive.trim().to_lowercase();
+      match directive.split_once('=') {
+        Some(("max-age", age)) => max_age = age.trim_matches('"').parse::<u64>().ok(),
+        _ if directive == "no-store" => return None,
+        // must-revalidate only forbids serving stale entries, which is never done
+        _ if directive == "no-cache" => no_cache = true,
+        _ => {}
+      }
+    }
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let age = header(header::AGE).and_then(|age| age.parse::<u64>().ok()).unwrap_or(0);
+    let expires = match max_age {
+      Some(max_age) => Some((now + max_age).saturating_sub(age)),
+      None => header(header::EXPIRES).map(|date| {
+        httpdate::parse_http_date(&date)
+          .map(|date| date.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+          .unwrap_or(0)
+      })
+    };
+    let metadata = Metadata {
+      url: normalize(url).to_string(),
+      status_code,
+      etag: header(header::ETAG),
+      last_modified: header(header::LAST_MODIFIED),
+      headers: headers.iter()
+                      .filter(|(name, _)| is_stored(name, headers))
+                      .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                      .collect(),
+      final_url: None,
+      expires,
+      no_cache
+    };
+    let reusable = metadata.is_fresh(now) || metadata.etag.is_some() || metadata.last_modified.is_some();
+    reusable.then_some(metadata)
+  }
+
+  /// The metadata after the server answered 304 with the headers, which 
+  /// replace the stored headers of the same name and renew the freshness. 
+  /// None if the entry may no longer be stored, such as after `no-store`.
+  pub(crate) fn revalidated(&self, url: &Url, headers: &HeaderMap) -> Option<Metadata> {
+    let mut merged = self.header_map();
+    for name in headers.keys() {
+      merged.remove(name);
+    }
+    for (name, value) in headers {
+      merged.append(name, value.clone());
+    }
+    let updated = Metadata::from_headers(url, self.status_code, &merged, SystemTime::now())?;
+    Some(Metadata { final_url: self.final_url.clone(), ..updated })
+  }
+
+  /// The stored headers, skipping any that are no longer valid
+  pub(crate) fn header_map(&self) -> HeaderMap {
+    self.headers.iter().filter_map(|(name, value)| {
+      let name = header::HeaderName::from_bytes(name.as_bytes()).ok()?;
+      let value = header::HeaderValue::from_str(value).ok()?;
+      Some((name, value))
+    }).collect()
+  }
+
+  /// Whether the entry can be used at the time, given as unix seconds, 
+  /// without asking the server
+  pub(crate) fn is_fresh(&self, now: u64) -> bool {
+    !self.no_cache && self.expires.is_some_and(|expires| now < expires)
+  }
+}
+
+impl Entry {
+  pub(crate) fn is_fresh(&self) -> bool {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs();
+    self.metadata.is_fresh(now)
+  }
+}
+
+// Whether a response header is kept with an entry, hop-by-hop headers only 
+// applied to the original connection and cookies or credentials must never 
+// be replayed from disk
+fn is_stored(name: &header::HeaderName, headers: &HeaderMap) -> bool {
+  let hop_by_hop = [
+    header::CONNECTION, header::TRANSFER_ENCODING, header::TE, header::TRAILER, header::UPGRADE,
+    header::PROXY_AUTHENTICATE, header::PROXY_AUTHORIZATION
+  ];
+  let sensitive = [header::SET_COOKIE, header::AUTHORIZATION];
+  let connection_listed = headers.get_all(header::CONNECTION).iter().any(|value| {
+    value.to_str().unwrap_or_default().split(',').any(|listed| listed.trim().eq_ignore_ascii_case(name.as_str()))
+  });
+  !hop_by_hop.contains(name)
+    && !sensitive.contains(name)
+    && !matches!(name.as_str(), "keep-alive" | "proxy-connection" | "set-cookie2")
+    && !connection_listed
+}
+
+// Persists an entry's recency as its body's modified time, best effort
+async fn touch_file(path: PathBuf) {
+  let _ = tokio::task::spawn_blocking(move || {
+    std::fs::File::options().write(true).open(path)?.set_modified(SystemTime::now())
+  }).await;
+}
+
+async fn remove_files(dir: &Path, key: &str) -> io::Result<()> {
+  for extension in ["body", "meta"] {
+    match tokio::fs::remove_file(dir.join(format!("{}.{}", key, extension))).await {
+      Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+// Drops the fragment and re-encodes each path segment, so spellings of the 
+// same url such as `ark:` and `ark%3A` share an entry
+fn normalize(url: &Url) -> Url {
+  let mut normalized = url.clone();
+  normalized.set_fragment(None);
+  let segments = url.path_segments().and_then(|segments| {
+    segments.map(|segment| percent_decode_str(segment).decode_utf8().ok().map(|s| s.into_owned()))
+            .collect::<Option<Vec<String>>>()
+  });
+  if let (Some(segments), Ok(mut path)) = (segments, normalized.path_segments_mut()) {
+    path.clear().extend(segments);
+  }
+  normalized
+}
+
+// A 64 bit FNV-1a hash of the url, stable across runs unlike the std hasher
+fn key(url: &Url) -> String {
+  let hash = url.as_str().bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+  });
+  format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use reqwest::header::HeaderValue;
+
+  fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+    pairs.iter().map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap())).collect()
+  }
+
+  #[test]
+  fn reads_cache_headers() {
+    let url = Url::parse("https://example.org/iiif/abc/info.json").unwrap();
+    let now = UNIX_EPOCH + Duration::from_secs(1000);
+
+    let fresh = Metadata::from_headers(&url, 200, &headers(&[(header::CACHE_CONTROL, "public, max-age=60"), (header::AGE, "10")]), now).unwrap();
+    assert_eq!(Some(1050), fresh.expires);
+    assert!(fresh.is_fresh(1049));
+    assert!(!fresh.is_fresh(1050));
+
+    let revalidate = Metadata::from_headers(&url, 200, &headers(&[(header::CACHE_CONTROL, "no-cache"), (header::ETAG, "\"v1\"")]), now).unwrap();
+    assert!(!revalidate.is_fresh(0));
+    assert_eq!(Some("\"v1\"".into()), revalidate.etag);
+
+    assert_eq!(None, Metadata::from_headers(&url, 200, &headers(&[(header::CACHE_CONTROL, "no-store, max-age=60")]), now));
+    assert_eq!(None, Metadata::from_headers(&url, 200, &HeaderMap::new(), now));
+
+    // must-revalidate only applies once the entry is stale
+    let must = Metadata::from_headers(&url, 200, &headers(&[(header::CACHE_CONTROL, "max-age=60, must-revalidate")]), now).unwrap();
+    assert!(must.is_fresh(1059));
+    assert!(!must.is_fresh(1060));
+  }
+
+  #[test]
+  fn stores_end_to_end_headers() {
+    let url = Url::parse("https://example.org/iiif/abc/info.json").unwrap();
+    let response = headers(&[
+      (header::CACHE_CONTROL, "max-age=60"),
+      (header::CONTENT_TYPE, "application/json"),
+      (header::SET_COOKIE, "session=secret"),
+      (header::AUTHORIZATION, "Bearer secret"),
+      (header::CONNECTION, "close, x-trace"),
+      (header::TRANSFER_ENCODING, "chunked"),
+      (header::HeaderName::from_static("keep-alive"), "timeout=5"),
+      (header::HeaderName::from_static("x-trace"), "abc")
+    ]);
+    let metadata = Metadata::from_headers(&url, 200, &response, UNIX_EPOCH).unwrap();
+    let names: Vec<&str> = metadata.headers.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(vec!["cache-control", "content-type"], names);
+  }
+
+  #[test]
+  fn normalizes_urls() {
+    let url = |s| Url::parse(s).unwrap();
+    let plain = normalize(&url("https://example.org/iiif/ark:/info.json"));
+    assert_eq!(plain, normalize(&url("https://EXAMPLE.org:443/iiif/ark%3A/info.json#top")));
+    assert_ne!(plain, normalize(&url("https://example.org/iiif/ark%3A%2F/info.json")));
+  }
+
+  #[tokio::test]
+  async fn evicts_least_recently_used() {
+    let dir = std::env::temp_dir().join(format!("iiif-cache-test-{}", std::process::id()));
+    let cache = DiskCache::open(&dir, 10).unwrap();
+    let fresh = headers(&[(header::CACHE_CONTROL, "max-age=60")]);
+    let url = |id| Url::parse(&format!("https://example.org/iiif/{}/info.json", id)).unwrap();
+
+    cache.put(&url("a"), &url("a"), 200, &fresh, &Bytes::from_static(b"aaaa")).await.unwrap();
+    cache.put(&url("b"), &url("b"), 200, &fresh, &Bytes::from_static(b"bbbb")).await.unwrap();
+    assert!(cache.get(&url("a")).await.is_some());
+    cache.put(&url("c"), &url("c"), 200, &fresh, &Bytes::from_static(b"cccc")).await.unwrap();
+
+    assert_eq!(8, cache.size());
+    assert!(cache.get(&url("b")).await.is_none());
+    assert_eq!(&b"aaaa"[..], &cache.get(&url("a")).await.unwrap().body[..]);
+
+    // Entries and their recency survive reopening the directory
+    let reopened = DiskCache::open(&dir, 10).unwrap();
+    assert_eq!(8, reopened.size());
+    assert!(reopened.get(&url("a")).await.unwrap().is_fresh());
+    let reopened = DiskCache::open(&dir, 10).unwrap();
+    reopened.put(&url("d"), &url("d"), 200, &fresh, &Bytes::from_static(b"dddd")).await.unwrap();
+    assert!(reopened.get(&url("c")).await.is_none());
+    assert!(reopened.get(&url("a")).await.is_some());
+    reopened.clear().await.unwrap();
+    assert_eq!(0, reopened.size());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[tokio::test]
+  async fn concurrent_puts_keep_one_body() {
+    let dir = std::env::temp_dir().join(format!("iiif-cache-race-{}", std::process::id()));
+    let cache = DiskCache::open(&dir, 1024 * 1024).unwrap();
+    let fresh = headers(&[(header::CACHE_CONTROL, "max-age=60")]);
+    let url = Url::parse("https://example.org/iiif/abc/info.json").unwrap();
+    let bodies: Vec<Bytes> = (0..8u8).map(|n| Bytes::from(vec![n; 64 * 1024])).collect();
+    futures::future::join_all(bodies.iter().map(|body| cache.put(&url, &url, 200, &fresh, body))).await;
+
+    let entry = cache.get(&url).await.unwrap();
+    assert!(bodies.contains(&entry.body));
+    assert!(std::fs::read_dir(&dir).unwrap().all(|file| file.unwrap().path().extension().is_some_and(|e| e != "tmp")));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,107 @@
+//! Rewrites requests into the canonical URI syntax
+use super::*;
+
+impl Image {
+  /// Rewrites the region and size into the canonical form for the image 
+  /// dimensions in the info document, so that equivalent requests share 
+  /// one url. The region becomes `full` or `x,y,w,h`, for 2.x the size 
+  /// becomes `full`, `w,` or `w,h` and for 3.0 `max`, `w,h` or `^w,h`. 
+  /// Rotation and quality are already written in their canonical form.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  /// 
+  /// // Both are rewritten to .../full/3000,/0/default.jpg for a 6000x4000 image
+  /// let mut a = Image::new("https://example.org/iiif");
+  /// a.pct_region(0.0, 0.0, 100.0, 100.0);
+  /// a.pct_size(50);
+  /// let mut b = Image::new("https://example.org/iiif");
+  /// b.less_than_width_height(3000, 3000);
+  /// a.canonicalize(&info)?;
+  /// b.canonicalize(&info)?;
+  /// assert_eq!(a, b);
+  /// ```
+  pub fn canonicalize(&mut self, info: &Info) -> Result<(), Violation> {
+    let dimensions = self.dimensions(info)?;
+    let region = dimensions.region;
+    let (width, height) = (dimensions.width, dimensions.height);
+    let full = region.x == 0 && region.y == 0 && region.w == info.width() && region.h == info.height();
+    let native = width == region.w && height == region.h;
+
+    self.size = match self.version {
+      Version::V2 if native => Size::Full,
+      Version::V2 => {
+        let keeps_ratio = (region.h as f64 * width as f64 / region.w as f64).round() as usize == height;
+        match keeps_ratio {
+          true => Size::W(width),
+          false => Size::WH(width, height)
+        }
+      }
+      Version::V3 => {
+        let mut max = self.clone();
+        max.size = Size::Max;
+        let max = max.resolve_size(&region, info)?;
+        match (width, height) {
+          dims if dims == max => Size::Max,
+          _ if width > region.w || height > region.h => Size::UpWH(width, height),
+          _ => Size::WH(width, height)
+        }
+      }
+    };
+    self.region = match full {
+      true => Region::Full,
+      false => Region::Abs(region)
+    };
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn info() -> Info {
+    Info::V2(InfoV2{ width: 6000, height: 4000, ..Default::default() })
+  }
+
+  #[test]
+  fn equivalent_requests_share_url() {
+    let mut pct = Image::new("https://example.org/iiif");
+    pct.identifier("abc");
+    pct.pct_region(50.0, 50.0, 50.0, 50.0);
+    pct.pct_size(50);
+    let mut abs = pct.clone();
+    abs.absolute_region(3000, 2000, 3000, 2000);
+    abs.less_than_width_height(1500, 5000);
+    pct.canonicalize(&info()).unwrap();
+    abs.canonicalize(&info()).unwrap();
+    assert_eq!("https://example.org/iiif/abc/3000,2000,3000,2000/1500,/0/default.jpg", pct.to_string());
+    assert_eq!(pct, abs);
+  }
+
+  #[test]
+  fn v2_full_and_distorted() {
+    let mut api = Image::new("https://example.org/iiif");
+    api.square_region();
+    api.width_height(4000, 4000);
+    api.canonicalize(&info()).unwrap();
+    assert_eq!(Region::Abs(Absolute{ x: 1000, y: 0, w: 4000, h: 4000 }), api.region);
+    assert_eq!(Size::Full, api.size);
+    api.width_height(400, 200);
+    api.canonicalize(&info()).unwrap();
+    assert_eq!(Size::WH(400, 200), api.size);
+  }
+
+  #[test]
+  fn v3_uses_max_and_width_height() {
+    let info = Info::V3(InfoV3{ width: 6000, height: 4000, max_width: Some(3000), ..Default::default() });
+    let mut api = Image::new("https://example.org/iiif");
+    api.version(Version::V3);
+    api.width(3000);
+    api.canonicalize(&info).unwrap();
+    assert_eq!((Region::Full, Size::Max), (api.region.clone(), api.size.clone()));
+    api.height(100);
+    api.canonicalize(&info).unwrap();
+    assert_eq!(Size::WH(150, 100), api.size);
+  }
+}
@@ -0,0 +1,221 @@
+//! Compliance levels and the features, formats and qualities they imply
+use super::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// The compliance level a server claims in its profile. Parsed from 2.x 
+/// profile URIs such as `http://iiif.io/api/image/2/level2.json` or the 
+/// 3.0 names `level0`, `level1` and `level2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ComplianceLevel {
+  Level0,
+  Level1,
+  Level2
+}
+
+/// A named feature from the Image API, the variants cover both 2.1 and 3.0
+/// with any other feature names kept as Other.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Feature {
+  BaseUriRedirect,
+  CanonicalLinkHeader,
+  Cors,
+  JsonldMediaType,
+  Mirroring,
+  ProfileLinkHeader,
+  RegionByPct,
+  RegionByPx,
+  RegionSquare,
+  RotationArbitrary,
+  RotationBy90s,
+  SizeAboveFull,
+  SizeByConfinedWh,
+  SizeByDistortedWh,
+  SizeByForcedWh,
+  SizeByH,
+  SizeByPct,
+  SizeByW,
+  SizeByWh,
+  SizeUpscaling,
+  Other(String)
+}
+
+impl ComplianceLevel {
+  /// Parses a 2.x profile URI or a 3.0 level name, returning None for 
+  /// anything that does not name level 0, 1 or 2.
+  pub fn from_uri(uri: &str) -> Option<ComplianceLevel> {
+    let name = uri.trim_end_matches(".json");
+    let (_, level) = name.rsplit_once("level")?;
+    match level {
+      "0" => Some(ComplianceLevel::Level0),
+      "1" => Some(ComplianceLevel::Level1),
+      "2" => Some(ComplianceLevel::Level2),
+      _ => None
+    }
+  }
+
+  /// The features a server at this level must support
+  pub fn features(&self, version: Version) -> Vec<Feature> {
+    use Feature::*;
+    let mut features = match (self, version) {
+      (ComplianceLevel::Level0, _) => return Vec::new(),
+      (_, Version::V2) => vec![RegionByPx, SizeByW, SizeByH, SizeByPct],
+      (_, Version::V3) => vec![RegionByPx, RegionSquare, SizeByW, SizeByH, SizeByWh]
+    };
+    features.extend(vec![BaseUriRedirect, Cors, JsonldMediaType]);
+    if *self == ComplianceLevel::Level2 {
+      match version {
+        Version::V2 => features.extend(vec![RegionByPct, SizeByConfinedWh, SizeByDistortedWh, SizeByWh, RotationBy90s]),
+        Version::V3 => features.extend(vec![RegionByPct, SizeByPct, SizeByConfinedWh, RotationBy90s])
+      }
+    }
+    features
+  }
+
+  /// The formats a server at this level must support
+  pub fn formats(&self) -> Vec<Format> {
+    match self {
+      ComplianceLevel::Level2 => vec![Format::Jpg, Format::Png],
+      _ => vec![Format::Jpg]
+    }
+  }
+
+  /// The qualities a server at this level must support
+  pub fn qualities(&self, version: Version) -> Vec<Quality> {
+    match (self, version) {
+      (ComplianceLevel::Level2, Version::V2) => vec![Quality::ServerDefault, Quality::Color, Quality::Gray, Quality::Bitonal],
+      (ComplianceLevel::Level2, Version::V3) => vec![Quality::ServerDefault, Quality::Color],
+      _ => vec![Quality::ServerDefault]
+    }
+  }
+}
+
+impl Info {
+  /// The compliance level named in the profile
+  pub fn compliance_level(&self) -> Option<ComplianceLevel> {
+    ComplianceLevel::from_uri(self.profile_uri())
+  }
+
+  /// Whether the feature is implied by the compliance level or listed in 
+  /// the profile
+  pub fn supports_feature(&self, feature: &Feature) -> bool {
+    let implied = self.compliance_level()
+                      .is_some_and(|level| level.features(self.version()).contains(feature));
+    implied || self.supports().iter().any(|f| f.parse().as_ref() == Ok(feature))
+  }
+
+  /// Whether the format is implied by the compliance level or listed in 
+  /// the profile, level 0 is assumed when no level is given
+  pub fn supports_format(&self, format: &Format) -> bool {
+    let level = self.compliance_level().unwrap_or(ComplianceLevel::Level0);
+    level.formats().contains(format) || self.formats().iter().any(|f| f.parse().as_ref() == Ok(format))
+  }
+
+  /// Whether the quality is implied by the compliance level or listed in 
+  /// the profile, level 0 is assumed when no level is given
+  pub fn supports_quality(&self, quality: &Quality) -> bool {
+    let level = self.compliance_level().unwrap_or(ComplianceLevel::Level0);
+    level.qualities(self.version()).contains(quality) || self.qualities().iter().any(|q| q.parse().as_ref() == Ok(quality))
+  }
+}
+
+impl fmt::Display for ComplianceLevel {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ComplianceLevel::Level0 => write!(f, "level0"),
+      ComplianceLevel::Level1 => write!(f, "level1"),
+      ComplianceLevel::Level2 => write!(f, "level2")
+    }
+  }
+}
+
+impl fmt::Display for Feature {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      Feature::BaseUriRedirect => "baseUriRedirect",
+      Feature::CanonicalLinkHeader => "canonicalLinkHeader",
+      Feature::Cors => "cors",
+      Feature::JsonldMediaType => "jsonldMediaType",
+      Feature::Mirroring => "mirroring",
+      Feature::ProfileLinkHeader => "profileLinkHeader",
+      Feature::RegionByPct => "regionByPct",
+      Feature::RegionByPx => "regionByPx",
+      Feature::RegionSquare => "regionSquare",
+      Feature::RotationArbitrary => "rotationArbitrary",
+      Feature::RotationBy90s => "rotationBy90s",
+      Feature::SizeAboveFull => "sizeAboveFull",
+      Feature::SizeByConfinedWh => "sizeByConfinedWh",
+      Feature::SizeByDistortedWh => "sizeByDistortedWh",
+      Feature::SizeByForcedWh => "sizeByForcedWh",
+      Feature::SizeByH => "sizeByH",
+      Feature::SizeByPct => "sizeByPct",
+      Feature::SizeByW => "sizeByW",
+      Feature::SizeByWh => "sizeByWh",
+      Feature::SizeUpscaling => "sizeUpscaling",
+      Feature::Other(name) => name
+    };
+    write!(f, "{}", name)
+  }
+}
+
+/// Feature names never fail to parse, unknown names become Feature::Other
+impl FromStr for Feature {
+  type Err = std::convert::Infallible;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let feature = match s {
+      "baseUriRedirect" => Feature::BaseUriRedirect,
+      "canonicalLinkHeader" => Feature::CanonicalLinkHeader,
+      "cors" => Feature::Cors,
+      "jsonldMediaType" => Feature::JsonldMediaType,
+      "mirroring" => Feature::Mirroring,
+      "profileLinkHeader" => Feature::ProfileLinkHeader,
+      "regionByPct" => Feature::RegionByPct,
+      "regionByPx" => Feature::RegionByPx,
+      "regionSquare" => Feature::RegionSquare,
+      "rotationArbitrary" => Feature::RotationArbitrary,
+      "rotationBy90s" => Feature::RotationBy90s,
+      "sizeAboveFull" => Feature::SizeAboveFull,
+      "sizeByConfinedWh" => Feature::SizeByConfinedWh,
+      "sizeByDistortedWh" => Feature::SizeByDistortedWh,
+      "sizeByForcedWh" => Feature::SizeByForcedWh,
+      "sizeByH" => Feature::SizeByH,
+      "sizeByPct" => Feature::SizeByPct,
+      "sizeByW" => Feature::SizeByW,
+      "sizeByWh" => Feature::SizeByWh,
+      "sizeUpscaling" => Feature::SizeUpscaling,
+      _ => Feature::Other(s.into())
+    };
+    Ok(feature)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_profile_uris() {
+    assert_eq!(Some(ComplianceLevel::Level2), ComplianceLevel::from_uri("http://iiif.io/api/image/2/level2.json"));
+    assert_eq!(Some(ComplianceLevel::Level1), ComplianceLevel::from_uri("http://library.stanford.edu/iiif/image-api/1.1/compliance.html#level1"));
+    assert_eq!(Some(ComplianceLevel::Level0), ComplianceLevel::from_uri("level0"));
+    assert_eq!(None, ComplianceLevel::from_uri("http://iiif.io/api/image/2/context.json"));
+  }
+
+  #[test]
+  fn merges_level_and_profile() {
+    let info = Info::V3(InfoV3{
+      profile: "level1".into(),
+      extra_features: vec!["mirroring".into(), "regionByPctOfHeight".into()],
+      extra_formats: vec!["webp".into()],
+      ..Default::default()
+    });
+    assert!(info.supports_feature(&Feature::RegionSquare));
+    assert!(info.supports_feature(&Feature::Mirroring));
+    assert!(info.supports_feature(&Feature::Other("regionByPctOfHeight".into())));
+    assert!(!info.supports_feature(&Feature::RegionByPct));
+    assert!(info.supports_format(&Format::Webp));
+    assert!(!info.supports_format(&Format::Png));
+    assert!(!info.supports_quality(&Quality::Gray));
+  }
+}
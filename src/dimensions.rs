@@ -0,0 +1,175 @@
+//! Resolves the region and size parameters to the exact pixels the server returns
+use super::*;
+
+/// The output rectangle of an image request, the region in absolute pixels 
+/// of the full image and the width and height after scaling.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Dimensions {
+  pub region: Absolute,
+  pub width: usize,
+  pub height: usize
+}
+
+impl Image {
+  /// Computes the region in pixels and the final width and height the server 
+  /// should return for the current parameters, using the image dimensions 
+  /// and size limits from the info document. Regions running off the edge of
+  /// the image are clamped, rotation is not applied.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  /// 
+  /// let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+  /// api.identifier("25286607");
+  /// let info = api.clone().fetch_info().await?;
+  /// api.pct_region(0.0, 0.0, 50.0, 50.0);
+  /// api.width(500);
+  /// let dimensions = api.dimensions(&info.info)?;
+  /// ```
+  pub fn dimensions(&self, info: &Info) -> Result<Dimensions, Violation> {
+    let region = self.resolve_region(info.width(), info.height())?;
+    let (width, height) = self.resolve_size(&region, info)?;
+    Ok(Dimensions{ region, width, height })
+  }
+
+  pub(crate) fn resolve_region(&self, width: usize, height: usize) -> Result<Absolute, Violation> {
+    let region = match &self.region {
+      Region::Full => Absolute{ x: 0, y: 0, w: width, h: height },
+      Region::Square => {
+        let side = width.min(height);
+        Absolute{ x: (width - side) / 2, y: (height - side) / 2, w: side, h: side }
+      }
+      Region::Abs(abs) => abs.clone(),
+      Region::Pct(pct) => Absolute{
+        x: percent_of(pct.x, width),
+        y: percent_of(pct.y, height),
+        w: percent_of(pct.w, width),
+        h: percent_of(pct.h, height)
+      }
+    };
+    if region.x >= width || region.y >= height || region.w == 0 || region.h == 0 {
+      return Err(Violation::RegionOutOfBounds(region));
+    }
+    Ok(Absolute{
+      w: region.w.min(width - region.x),
+      h: region.h.min(height - region.y),
+      ..region
+    })
+  }
+
+  pub(crate) fn resolve_size(&self, region: &Absolute, info: &Info) -> Result<(usize, usize), Violation> {
+    let (rw, rh) = (region.w as f64, region.h as f64);
+    let scale = |s: f64| ((rw * s).round() as usize, (rh * s).round() as usize);
+    let limit = max_scale(rw, rh, info);
+    let (width, height) = match self.size {
+      // Full is sent as max for 3.0
+      Size::Full if self.version == Version::V2 => (region.w, region.h),
+      Size::Full | Size::Max => scale(limit.unwrap_or(1.0).min(1.0)),
+      Size::UpMax => scale(limit.unwrap_or(1.0)),
+      Size::W(w) | Size::UpW(w) => (w, (rh * w as f64 / rw).round() as usize),
+      Size::H(h) | Size::UpH(h) => ((rw * h as f64 / rh).round() as usize, h),
+      Size::Pct(n) | Size::UpPct(n) => scale(n as f64 / 100.0),
+      Size::WH(w, h) | Size::UpWH(w, h) => (w, h),
+      Size::LtWH(w, h) => {
+        let fit = (w as f64 / rw).min(h as f64 / rh);
+        match self.version {
+          Version::V2 => scale(fit),
+          Version::V3 => scale(fit.min(1.0))
+        }
+      }
+      Size::UpLtWH(w, h) => scale((w as f64 / rw).min(h as f64 / rh))
+    };
+
+    if width == 0 || height == 0 {
+      return Err(Violation::EmptySize);
+    }
+    let upscaled = width > region.w || height > region.h;
+    if self.version == Version::V3 && upscaled && !self.size.is_upscaled() {
+      return Err(Violation::UpscalingRequired{ width, height });
+    }
+    let above_max = info.max_width().is_some_and(|max| width > max)
+                    || info.max_height().is_some_and(|max| height > max)
+                    || info.max_area().is_some_and(|max| (width as u64).saturating_mul(height as u64) > max);
+    match above_max {
+      true => Err(Violation::AboveMaximum{ width, height }),
+      false => Ok((width, height))
+    }
+  }
+}
+
+// Round a percentage of a pixel length to the nearest pixel
+fn percent_of(pct: f32, length: usize) -> usize {
+  (pct as f64 / 100.0 * length as f64).round() as usize
+}
+
+// The largest scale factor allowed by maxWidth, maxHeight and maxArea.
+// Per the spec maxHeight defaults to maxWidth when only the width is given.
+fn max_scale(rw: f64, rh: f64, info: &Info) -> Option<f64> {
+  let max_height = info.max_height().or_else(|| info.max_width());
+  [
+    info.max_width().map(|max| max as f64 / rw),
+    max_height.map(|max| max as f64 / rh),
+    info.max_area().map(|max| (max as f64 / (rw * rh)).sqrt())
+  ].iter()
+   .flatten()
+   .cloned()
+   .reduce(f64::min)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn info() -> Info {
+    Info::V3(InfoV3{ width: 6000, height: 4000, max_width: Some(3000), ..Default::default() })
+  }
+
+  #[test]
+  fn square_is_centered() {
+    let mut api = Image::new("https://example.org/iiif");
+    api.square_region();
+    api.pct_size(50);
+    let dimensions = api.dimensions(&info()).unwrap();
+    assert_eq!(Absolute{ x: 1000, y: 0, w: 4000, h: 4000 }, dimensions.region);
+  }
+
+  #[test]
+  fn clamps_region() {
+    let mut api = Image::new("https://example.org/iiif");
+    api.absolute_region(5000, 3000, 2000, 2000);
+    api.pct_size(50);
+    let dimensions = api.dimensions(&info()).unwrap();
+    assert_eq!(Absolute{ x: 5000, y: 3000, w: 1000, h: 1000 }, dimensions.region);
+    assert_eq!((500, 500), (dimensions.width, dimensions.height));
+    api.absolute_region(6000, 0, 10, 10);
+    assert!(matches!(api.dimensions(&info()), Err(Violation::RegionOutOfBounds(_))));
+  }
+
+  #[test]
+  fn max_honours_limits() {
+    let mut api = Image::new("https://example.org/iiif");
+    api.max_size();
+    let dimensions = api.dimensions(&info()).unwrap();
+    assert_eq!((3000, 2000), (dimensions.width, dimensions.height));
+    api.width(4000);
+    assert_eq!(Err(Violation::AboveMaximum{ width: 4000, height: 2667 }), api.dimensions(&info()));
+  }
+
+  #[test]
+  fn best_fit_keeps_aspect_ratio() {
+    let mut api = Image::new("https://example.org/iiif");
+    api.pct_region(0.0, 0.0, 50.0, 50.0);
+    api.less_than_width_height(300, 300);
+    let dimensions = api.dimensions(&info()).unwrap();
+    assert_eq!(Absolute{ x: 0, y: 0, w: 3000, h: 2000 }, dimensions.region);
+    assert_eq!((300, 200), (dimensions.width, dimensions.height));
+  }
+
+  #[test]
+  fn huge_area_is_above_maximum() {
+    let info = Info::V3(InfoV3{ width: 6000, height: 4000, max_area: Some(1000), ..Default::default() });
+    let mut api = Image::new("https://example.org/iiif");
+    api.size = "4294967296,4294967296".parse().unwrap();
+    assert_eq!(Err(Violation::AboveMaximum{ width: 1 << 32, height: 1 << 32 }), api.dimensions(&info));
+  }
+}
@@ -0,0 +1,152 @@
+//! Streaming image downloads written straight to disk
+use super::*;
+use crate::errors::*;
+use crate::media::{verify_format, SNIFF_LEN};
+use crate::requests::status_error;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Limits for a streaming download, its progress is reported to the 
+/// session's `Progress` observer
+#[derive(Clone, Debug, Default)]
+pub struct DownloadOptions {
+  pub max_size: Option<u64>
+}
+
+/// Where a streamed image was written along with its size, the requested 
+/// and final url, status code returned and the format it was detected as
+#[derive(Clone, Debug)]
+pub struct Download {
+  pub status_code: u16,
+  pub url: Url,
+  pub path: PathBuf,
+  pub size: u64,
+  pub format: Option<Format>,
+  /// The url after any redirects
+  pub final_url: Url
+}
+
+impl DownloadOptions {
+  /// Fails the download with `Error::TooLarge` once the body exceeds the 
+  /// size, checked against the Content-Length before anything is written
+  pub fn max_size(&mut self, max_size: u64) {
+    self.max_size = Some(max_size);
+  }
+}
+
+impl Image {
+  /// Streams the image to a file at the path without holding the body in 
+  /// memory, making a single attempt. See `download_with` for retries, 
+  /// progress and a size cap.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  ///
+  /// let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+  /// api.identifier("25286607");
+  /// api.tif();
+  /// let download = api.download(&Client::new(), "master.tif").await?;
+  /// ```
+  pub async fn download<P: AsRef<Path>>(self, client: &Client, path: P) -> Result<Download, Error> {
+    let mut session = Session::new(client);
+    session.retry(RetryPolicy::none());
+    self.download_with(&session, path, &DownloadOptions::default()).await
+  }
+
+  /// Streams the image to a temporary file beside the path using the 
+  /// session's client, retry policy and rate limiter, then renames it into 
+  /// place once complete so a partial file is never left at the path. The 
+  /// start of the body is checked against the requested format before 
+  /// anything is kept. Downloads bypass the session's cache.
+  pub async fn download_with<P: AsRef<Path>>(self, session: &Session, path: P, options: &DownloadOptions) -> Result<Download, Error> {
+    let url = self.image_url()?;
+    let path = path.as_ref();
+    let tmp = temp_path(path);
+    let result = match session.retry.run(|| stream_to(session, &url, self.format, &tmp, options)).await {
+      Ok(Streamed{ status_code, size, format, final_url }) => tokio::fs::rename(&tmp, path)
+                                                                 .await
+                                                                 .map(|_| Download{ status_code, url: url.clone(), path: path.into(), size, format, final_url })
+                                                                 .map_err(Error::from),
+      Err(e) => Err(e)
+    };
+    if result.is_err() {
+      let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    session.report_result(&url, &result, |download| download.size);
+    result
+  }
+}
+
+// What a single successful attempt streamed into the file
+struct Streamed {
+  status_code: u16,
+  size: u64,
+  format: Option<Format>,
+  final_url: Url
+}
+
+// A single attempt at streaming the body into the file, which is truncated 
+// first so a retry starts over
+async fn stream_to(session: &Session, url: &Url, expected: Format, path: &Path, options: &DownloadOptions) -> Result<Streamed, Error> {
+  let _permit = session.throttle(url).await;
+  let mut response = session.client.get(url.clone())
+                                   .send()
+                                   .await
+                                   .map_err(|source| Error::Transport { url: url.clone(), source })?;
+  let status_code = response.status().as_u16();
+  if !response.status().is_success() {
+    return Err(status_error(url, &response))
+  }
+  let final_url = response.url().clone();
+  let total = response.content_length();
+  let too_large = |size: u64| options.max_size.is_some_and(|max_size| size > max_size);
+  if let Some(total) = total.filter(|total| too_large(*total)) {
+    return Err(Error::TooLarge { url: url.clone(), max_size: options.max_size.unwrap_or(total) })
+  }
+
+  session.report(|observer| observer.started(url, total));
+  let mut file = tokio::fs::File::create(path).await?;
+  let content_type = response.headers()
+                             .get(reqwest::header::CONTENT_TYPE)
+                             .and_then(|value| value.to_str().ok())
+                             .map(|value| value.to_string());
+  // The start of the body is held back until there is enough to sniff
+  let mut head = Vec::with_capacity(SNIFF_LEN);
+  let mut format = None;
+  let mut verified = false;
+  let mut received = 0;
+  while let Some(chunk) = response.chunk()
+                                  .await
+                                  .map_err(|source| Error::Transport { url: url.clone(), source })? {
+    received += chunk.len() as u64;
+    if too_large(received) {
+      return Err(Error::TooLarge { url: url.clone(), max_size: options.max_size.unwrap_or(received) })
+    }
+    match verified {
+      true => file.write_all(&chunk).await?,
+      false => {
+        head.extend_from_slice(&chunk);
+        if head.len() >= SNIFF_LEN {
+          format = verify_format(url, expected, content_type.as_deref(), &head)?;
+          file.write_all(&head).await?;
+          verified = true;
+        }
+      }
+    }
+    session.report(|observer| observer.received(url, received, total));
+  }
+  // A short or empty body is checked once it has all arrived
+  if !verified {
+    format = verify_format(url, expected, content_type.as_deref(), &head)?;
+    file.write_all(&head).await?;
+  }
+  file.flush().await?;
+  file.sync_all().await?;
+  Ok(Streamed{ status_code, size: received, format, final_url })
+}
+
+// A hidden file in the same directory so the rename stays on one filesystem
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!(".{}.part", name))
+}
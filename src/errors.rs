@@ -1,19 +1,146 @@
-//! Server response errors
-use std::error::Error;
+//! The crate error type along with server response and parameter parsing errors
+use crate::parameters::Format;
+use reqwest::Url;
+use std::error::Error as StdError;
 use std::fmt;
+use std::io;
+use std::time::Duration;
 
-/// Holds the status code and IIIF error description
+/// Every way a request can fail, wrapping the underlying source error. All 
+/// variants that occur after building the url carry the requested url.
 #[derive(Debug)]
+pub enum Error {
+  /// The request could not be sent or the response body could not be read
+  Transport { url: Url, source: reqwest::Error },
+  /// The server responded with a non success status code
+  Response { url: Url, source: Box<ResponseError> },
+  /// The info.json response could not be deserialized
+  Json { url: Url, source: serde_json::Error },
+  /// The request url could not be built from the host and parameters
+  Url { host: String, details: String },
+  /// Reading or writing a file failed
+  Io(io::Error),
+  /// An Image API url or parameter could not be parsed
+  Parse(ParseError),
+  /// A streamed download exceeded its size cap
+  TooLarge { url: Url, max_size: u64 },
+  /// The response body is not the requested format, such as an HTML error 
+  /// page returned with a success status by a misconfigured proxy
+  FormatMismatch { url: Url, expected: Format, found: Option<Format>, content_type: Option<String> },
+  /// A tile could not be decoded as an image
+  #[cfg(feature = "stitch")]
+  Decode { url: Url, source: Box<image::ImageError> },
+  /// No tile description in the info document lists the scale factor
+  #[cfg(feature = "stitch")]
+  NoTiles { scale_factor: usize }
+}
+
+impl Error {
+  /// Whether the same request may succeed if tried again later, this covers 
+  /// connection failures, timeouts, rate limiting and temporary server errors.
+  pub fn is_retryable(&self) -> bool {
+    match self {
+      Error::Transport { source, .. } => !source.is_builder() && !source.is_redirect(),
+      Error::Response { source, .. } => matches!(source.status_code, 429 | 500 | 502 | 503 | 504),
+      _ => false
+    }
+  }
+
+  /// The HTTP status code returned by the server, if a response was received
+  pub fn status_code(&self) -> Option<u16> {
+    match self {
+      Error::Transport { source, .. } => source.status().map(|s| s.as_u16()),
+      Error::Response { source, .. } => Some(source.status_code),
+      _ => None
+    }
+  }
+
+  /// How long the server asked the client to wait before retrying
+  pub fn retry_after(&self) -> Option<Duration> {
+    match self {
+      Error::Response { source, .. } => source.retry_after,
+      _ => None
+    }
+  }
+
+  /// The url that was requested
+  pub fn url(&self) -> Option<&Url> {
+    match self {
+      Error::Transport { url, .. } | Error::Response { url, .. } | Error::Json { url, .. } => Some(url),
+      Error::TooLarge { url, .. } | Error::FormatMismatch { url, .. } => Some(url),
+      #[cfg(feature = "stitch")]
+      Error::Decode { url, .. } => Some(url),
+      _ => None
+    }
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Transport { url, source } => write!(f, "Request to {} failed: {}", url, source),
+      Error::Response { url, source } => write!(f, "{} returned {}", url, source),
+      Error::Json { url, source } => write!(f, "Invalid info.json from {}: {}", url, source),
+      Error::Url { host, details } => write!(f, "Cannot build a url from host {:?}: {}", host, details),
+      Error::Io(e) => write!(f, "{}", e),
+      Error::Parse(e) => write!(f, "{}", e),
+      Error::TooLarge { url, max_size } => write!(f, "{} is larger than the {} byte limit", url, max_size),
+      Error::FormatMismatch { url, expected, found, content_type } => {
+        let found = found.map(|format| format.to_string())
+                         .or_else(|| content_type.clone())
+                         .unwrap_or_else(|| "an unknown format".into());
+        write!(f, "{} returned {} instead of {}", url, found, expected)
+      }
+      #[cfg(feature = "stitch")]
+      Error::Decode { url, source } => write!(f, "Cannot decode the tile from {}: {}", url, source),
+      #[cfg(feature = "stitch")]
+      Error::NoTiles { scale_factor } => write!(f, "No tiles are available at scale factor {}", scale_factor)
+    }
+  }
+}
+
+impl StdError for Error {
+  fn source(&self) -> Option<&(dyn StdError + 'static)> {
+    match self {
+      Error::Transport { source, .. } => Some(source),
+      Error::Response { source, .. } => Some(source.as_ref()),
+      Error::Json { source, .. } => Some(source),
+      Error::Url { .. } => None,
+      Error::Io(e) => Some(e),
+      Error::Parse(e) => Some(e),
+      Error::TooLarge { .. } | Error::FormatMismatch { .. } => None,
+      #[cfg(feature = "stitch")]
+      Error::Decode { source, .. } => Some(source.as_ref()),
+      #[cfg(feature = "stitch")]
+      Error::NoTiles { .. } => None
+    }
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(e: io::Error) -> Self { Error::Io(e) }
+}
+
+impl From<ParseError> for Error {
+  fn from(e: ParseError) -> Self { Error::Parse(e) }
+}
+
+/// Holds the status code and IIIF error description, along with how long 
+/// the server asked the client to wait before retrying if it sent a 
+/// Retry-After header
+#[derive(Debug, Clone, PartialEq)]
 pub struct ResponseError {
     pub status_code: u16,
-    pub details: String
+    pub details: String,
+    pub retry_after: Option<Duration>
 }
 
 impl ResponseError {
   pub fn new(status_code: u16) -> ResponseError {
     ResponseError{
       status_code,
-      details: parse_status(status_code)
+      details: parse_status(status_code),
+      retry_after: None
     }
   }
 }
@@ -24,11 +151,7 @@ impl fmt::Display for ResponseError {
   }
 }
 
-impl Error for ResponseError  {
-  fn description(&self) -> &str {
-      &self.details
-  }
-}
+impl StdError for ResponseError {}
 
 fn parse_status(status_code: u16) -> String {
   match status_code {
@@ -36,9 +159,88 @@ fn parse_status(status_code: u16) -> String {
     401 => "Authentication is required and not provided. See https://iiif.io/api/image/2.1/#authentication".into(),
     403 => "The user, authenticated or not, is not permitted to perform the requested operation.".into(),
     404 => "The image resource specified by identifier does not exist, the value of one or more of the parameters is not supported for this image, or the requested size is greater than the limits specified.".into(),
+    429 => "The client has sent too many requests in a given amount of time.".into(),
     500 => "The server encountered an unexpected error that prevented it from fulfilling the request.".into(),
     501 => "The server received a valid IIIF request that is not implemented.".into(),
+    502 => "A proxy or gateway in front of the server received an invalid response.".into(),
     503 => "The server is busy/temporarily unavailable due to load/maintenance issues.".into(),
+    504 => "A proxy or gateway in front of the server did not receive a timely response.".into(),
     _ => "Unspecified Error, check status code".into()
   }
-}
\ No newline at end of file
+}
+
+/// The part of an image request that failed to parse
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+  Url,
+  Identifier,
+  Region,
+  Size,
+  Rotation,
+  Quality,
+  Format
+}
+
+/// Holds the parameter that failed to parse, the offending value and a 
+/// description of what was wrong with it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+  pub parameter: Parameter,
+  pub value: String,
+  pub details: String
+}
+
+impl ParseError {
+  pub fn new(parameter: Parameter, value: &str, details: &str) -> ParseError {
+    ParseError{
+      parameter,
+      value: value.into(),
+      details: details.into()
+    }
+  }
+}
+
+impl fmt::Display for Parameter {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      Parameter::Url => "url",
+      Parameter::Identifier => "identifier",
+      Parameter::Region => "region",
+      Parameter::Size => "size",
+      Parameter::Rotation => "rotation",
+      Parameter::Quality => "quality",
+      Parameter::Format => "format"
+    };
+    write!(f, "{}", name)
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Invalid {} parameter {:?}: {}", self.parameter, self.value, self.details)
+  }
+}
+
+impl StdError for ParseError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn error_is_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Error>();
+  }
+
+  #[test]
+  fn retryable_status() {
+    let url = Url::parse("https://example.org/iiif/abc/info.json").unwrap();
+    let busy = Error::Response { url: url.clone(), source: Box::new(ResponseError::new(503)) };
+    let missing = Error::Response { url: url.clone(), source: Box::new(ResponseError::new(404)) };
+    assert!(busy.is_retryable());
+    assert!(!missing.is_retryable());
+    assert_eq!(Some(404), missing.status_code());
+    assert_eq!(Some(&url), missing.url());
+  }
+}
@@ -0,0 +1,142 @@
+//! Typed access to the response headers the Image API relies on
+use super::*;
+use reqwest::header::{self, HeaderMap};
+
+/// A single link from a Link header, such as the canonical image url or the 
+/// compliance profile the Image API requires servers to send
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+  pub uri: String,
+  /// The lowercased relation types, a link may have several
+  pub rel: Vec<String>,
+  /// Any other parameters with their quotes removed
+  pub params: Vec<(String, String)>
+}
+
+impl Link {
+  /// Whether the link has the relation type, ignoring case
+  pub fn has_rel(&self, rel: &str) -> bool {
+    self.rel.iter().any(|r| r.eq_ignore_ascii_case(rel))
+  }
+}
+
+/// Accessors for the headers kept on `Response` and `InfoResponse`
+pub trait ResponseHeaders {
+  /// Every header the server sent
+  fn headers(&self) -> &HeaderMap;
+
+  /// A header's value, if it was sent as visible ASCII
+  fn header(&self, name: &str) -> Option<&str> {
+    self.headers().get(name).and_then(|value| value.to_str().ok())
+  }
+
+  fn content_type(&self) -> Option<&str> {
+    self.header(header::CONTENT_TYPE.as_str())
+  }
+
+  fn etag(&self) -> Option<&str> {
+    self.header(header::ETAG.as_str())
+  }
+
+  fn last_modified(&self) -> Option<&str> {
+    self.header(header::LAST_MODIFIED.as_str())
+  }
+
+  /// The CORS header servers should send so browser clients can use images
+  fn access_control_allow_origin(&self) -> Option<&str> {
+    self.header(header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str())
+  }
+
+  /// Every link across all Link headers
+  fn links(&self) -> Vec<Link> {
+    self.headers()
+        .get_all(header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_links)
+        .collect()
+  }
+
+  /// The canonical url of the image, from `Link: <...>;rel="canonical"`
+  fn canonical_link(&self) -> Option<String> {
+    self.links().into_iter().find(|link| link.has_rel("canonical")).map(|link| link.uri)
+  }
+
+  /// The compliance level the image is served at, from `Link: <...>;rel="profile"`
+  fn profile_link(&self) -> Option<String> {
+    self.links().into_iter().find(|link| link.has_rel("profile")).map(|link| link.uri)
+  }
+}
+
+impl ResponseHeaders for Response {
+  fn headers(&self) -> &HeaderMap {
+    &self.headers
+  }
+}
+
+impl ResponseHeaders for InfoResponse {
+  fn headers(&self) -> &HeaderMap {
+    &self.headers
+  }
+}
+
+/// Parses a Link header value as described in RFC 8288, commas and 
+/// semicolons inside the angle brackets or quoted strings are not separators
+pub fn parse_links(value: &str) -> Vec<Link> {
+  split_outside(value, ',').into_iter().filter_map(|link| {
+    let mut parts = split_outside(link, ';').into_iter();
+    let uri = parts.next()?.trim();
+    let uri = uri.strip_prefix('<')?.strip_suffix('>')?.to_string();
+    let mut rel = Vec::new();
+    let mut params = Vec::new();
+    for param in parts {
+      let (name, value) = param.split_once('=').unwrap_or((param, ""));
+      let name = name.trim().to_lowercase();
+      let value = value.trim().trim_matches('"').to_string();
+      match name.as_str() {
+        "rel" => rel.extend(value.split_whitespace().map(|r| r.to_lowercase())),
+        _ => params.push((name, value))
+      }
+    }
+    Some(Link{ uri, rel, params })
+  }).collect()
+}
+
+// Splits on the separator unless it's within <...> or "..."
+fn split_outside(value: &str, separator: char) -> Vec<&str> {
+  let mut parts = Vec::new();
+  let (mut start, mut angled, mut quoted, mut escaped) = (0, false, false, false);
+  for (i, c) in value.char_indices() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' if quoted => escaped = true,
+      '"' if !angled => quoted = !quoted,
+      '<' if !quoted => angled = true,
+      '>' if !quoted => angled = false,
+      c if c == separator && !angled && !quoted => {
+        parts.push(&value[start..i]);
+        start = i + c.len_utf8();
+      }
+      _ => {}
+    }
+  }
+  parts.push(&value[start..]);
+  parts.into_iter().filter(|part| !part.trim().is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_link_headers() {
+    let links = parse_links(r#"<http://iiif.io/api/image/2/level1.json>;rel="profile", <https://example.org/iiif/a,b/full/full/0/default.jpg>; rel="canonical alternate"; title="a; b""#);
+    assert_eq!(2, links.len());
+    assert_eq!("http://iiif.io/api/image/2/level1.json", links[0].uri);
+    assert!(links[0].has_rel("Profile"));
+    assert_eq!("https://example.org/iiif/a,b/full/full/0/default.jpg", links[1].uri);
+    assert_eq!(vec!["canonical", "alternate"], links[1].rel);
+    assert_eq!(vec![("title".to_string(), "a; b".to_string())], links[1].params);
+    assert!(parse_links("not a link").is_empty());
+  }
+}
@@ -0,0 +1,541 @@
+//! The image information document returned from info.json requests
+use super::*;
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde_json::{Map, Value};
+
+/// The image information document, either the 2.x or 3.0 form depending on 
+/// what the server returned. The version is detected from the `@context`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Info {
+  V2(InfoV2),
+  V3(InfoV3)
+}
+
+impl Default for Info {
+  fn default() -> Self { Info::V2(InfoV2::default()) }
+}
+
+impl Info {
+  /// The version of the Image API the document conforms to
+  pub fn version(&self) -> Version {
+    match self {
+      Info::V2(_) => Version::V2,
+      Info::V3(_) => Version::V3
+    }
+  }
+
+  /// The base URI of the image service
+  pub fn id(&self) -> &str {
+    match self {
+      Info::V2(info) => &info.id,
+      Info::V3(info) => &info.id
+    }
+  }
+
+  /// The width in pixels of the full image content
+  pub fn width(&self) -> usize {
+    match self {
+      Info::V2(info) => info.width,
+      Info::V3(info) => info.width
+    }
+  }
+
+  /// The height in pixels of the full image content
+  pub fn height(&self) -> usize {
+    match self {
+      Info::V2(info) => info.height,
+      Info::V3(info) => info.height
+    }
+  }
+
+  /// The preferred sizes for complete images
+  pub fn sizes(&self) -> &[InfoSize] {
+    match self {
+      Info::V2(info) => &info.sizes,
+      Info::V3(info) => &info.sizes
+    }
+  }
+
+  /// The tile descriptions
+  pub fn tiles(&self) -> &[Tile] {
+    match self {
+      Info::V2(info) => &info.tiles,
+      Info::V3(info) => &info.tiles
+    }
+  }
+
+  /// The services embedded in the document
+  pub fn services(&self) -> &[InfoService] {
+    match self {
+      Info::V2(info) => &info.service,
+      Info::V3(info) => &info.service
+    }
+  }
+
+  /// The compliance level the server claims, either a 2.x profile URI 
+  /// or a 3.0 level name
+  pub fn profile_uri(&self) -> &str {
+    match self {
+      Info::V2(info) => info.profile_uri(),
+      Info::V3(info) => &info.profile
+    }
+  }
+
+  /// The formats listed in the profile, or extraFormats for 3.0
+  pub fn formats(&self) -> Vec<String> {
+    match self {
+      Info::V2(info) => info.profiles().flat_map(|p| p.formats.iter().cloned()).collect(),
+      Info::V3(info) => info.extra_formats.clone()
+    }
+  }
+
+  /// The qualities listed in the profile, or extraQualities for 3.0
+  pub fn qualities(&self) -> Vec<String> {
+    match self {
+      Info::V2(info) => info.profiles().flat_map(|p| p.qualities.iter().cloned()).collect(),
+      Info::V3(info) => info.extra_qualities.clone()
+    }
+  }
+
+  /// The features listed in the profile, or extraFeatures for 3.0
+  pub fn supports(&self) -> Vec<String> {
+    match self {
+      Info::V2(info) => info.profiles().flat_map(|p| p.supports.iter().cloned()).collect(),
+      Info::V3(info) => info.extra_features.clone()
+    }
+  }
+
+  /// The maximum width in pixels the server will return
+  pub fn max_width(&self) -> Option<usize> {
+    match self {
+      Info::V2(info) => info.profiles().find_map(|p| p.max_width),
+      Info::V3(info) => info.max_width
+    }
+  }
+
+  /// The maximum height in pixels the server will return
+  pub fn max_height(&self) -> Option<usize> {
+    match self {
+      Info::V2(info) => info.profiles().find_map(|p| p.max_height),
+      Info::V3(info) => info.max_height
+    }
+  }
+
+  /// The maximum area in pixels the server will return
+  pub fn max_area(&self) -> Option<u64> {
+    match self {
+      Info::V2(info) => info.profiles().find_map(|p| p.max_area),
+      Info::V3(info) => info.max_area
+    }
+  }
+}
+
+impl InfoV2 {
+  /// The compliance level URI, the first string in the profile
+  pub fn profile_uri(&self) -> &str {
+    self.profile.iter()
+                .find_map(|entry| match entry {
+                  ProfileEntry::Uri(uri) => Some(uri.as_str()),
+                  _ => None
+                })
+                .unwrap_or_default()
+  }
+
+  /// The profile objects listing the formats, qualities and features 
+  /// supported beyond the compliance level
+  pub fn profiles(&self) -> impl Iterator<Item = &Profile> {
+    self.profile.iter().filter_map(|entry| match entry {
+      ProfileEntry::Object(profile) => Some(profile),
+      _ => None
+    })
+  }
+}
+
+// Accepts either a single value or an array of values
+pub(crate) fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de>
+{
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum OneOrMany<T> {
+    // Many is tried first as some values, such as services, accept arrays
+    Many(Vec<T>),
+    One(T)
+  }
+  match OneOrMany::deserialize(deserializer)? {
+    OneOrMany::One(value) => Ok(vec![value]),
+    OneOrMany::Many(values) => Ok(values)
+  }
+}
+
+impl<'de> Deserialize<'de> for Info {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    match Version::detect(&value) {
+      Version::V2 => serde_json::from_value(value).map(Info::V2),
+      Version::V3 => serde_json::from_value(value).map(Info::V3)
+    }.map_err(de::Error::custom)
+  }
+}
+
+impl Serialize for Info {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    match self {
+      Info::V2(info) => info.serialize(serializer),
+      Info::V3(info) => info.serialize(serializer)
+    }
+  }
+}
+
+impl Version {
+  // The 3.0 context is required to be the last, or only, item in @context
+  fn detect(info: &Value) -> Version {
+    let is_v3 = |context: &Value| context.as_str()
+                                         .is_some_and(|c| c.contains("iiif.io/api/image/3"));
+    let v3 = match &info["@context"] {
+      Value::Array(contexts) => contexts.iter().any(is_v3),
+      context => is_v3(context)
+    };
+    match v3 {
+      true => Version::V3,
+      false => Version::V2
+    }
+  }
+}
+
+/// An Image API 2.x information document. Only the id, width and height are 
+/// required, any properties not modelled here are kept in `extra` and written 
+/// back out when serializing.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InfoV2 {
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  #[serde(rename = "@context")]
+  pub context: String,
+  #[serde(rename = "@id")]
+  pub id: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub protocol: Option<String>,
+  pub width: usize,
+  pub height: usize,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub sizes: Vec<InfoSize>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub tiles: Vec<Tile>,
+  #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+  pub attribution: Vec<Attribution>,
+  #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+  pub logo: Vec<Logo>,
+  #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+  pub license: Vec<License>,
+  #[serde(default, deserialize_with = "one_or_many")]
+  pub profile: Vec<ProfileEntry>,
+  #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+  pub service: Vec<InfoService>,
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InfoSize {
+  pub width: usize,
+  pub height: usize,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tile {
+  pub width: usize,
+  #[serde(rename = "scaleFactors")]
+  pub scale_factors: Vec<usize>,
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub height: usize,
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
+}
+
+fn is_zero(n: &usize) -> bool {
+  *n == 0
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "AttributionRepr", into = "AttributionRepr")]
+pub struct Attribution {
+  pub value: String,
+  pub language: Option<String>,
+}
+
+// Attribution is either a plain string or a language tagged value
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum AttributionRepr {
+  Plain(String),
+  Tagged {
+    #[serde(rename = "@value")]
+    value: String,
+    #[serde(rename = "@language", default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>
+  }
+}
+
+impl From<AttributionRepr> for Attribution {
+  fn from(repr: AttributionRepr) -> Self {
+    match repr {
+      AttributionRepr::Plain(value) => Attribution{ value, language: None },
+      AttributionRepr::Tagged{ value, language } => Attribution{ value, language }
+    }
+  }
+}
+
+impl From<Attribution> for AttributionRepr {
+  fn from(attribution: Attribution) -> Self {
+    match attribution.language {
+      None => AttributionRepr::Plain(attribution.value),
+      language => AttributionRepr::Tagged{ value: attribution.value, language }
+    }
+  }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "LogoRepr", into = "LogoRepr")]
+pub struct Logo {
+  pub id: String,
+  pub service: Vec<Service>,
+}
+
+// Logo is either the image URI or an object with an optional service
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum LogoRepr {
+  Uri(String),
+  Object {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    service: Vec<Service>
+  }
+}
+
+impl From<LogoRepr> for Logo {
+  fn from(repr: LogoRepr) -> Self {
+    match repr {
+      LogoRepr::Uri(id) => Logo{ id, service: Vec::new() },
+      LogoRepr::Object{ id, service } => Logo{ id, service }
+    }
+  }
+}
+
+impl From<Logo> for LogoRepr {
+  fn from(logo: Logo) -> Self {
+    match logo.service.is_empty() {
+      true => LogoRepr::Uri(logo.id),
+      false => LogoRepr::Object{ id: logo.id, service: logo.service }
+    }
+  }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "LicenseRepr", into = "LicenseRepr")]
+pub struct License {
+  pub id: String,
+  pub extra: Map<String, Value>,
+}
+
+// License is either the URI of the license or an object describing it
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum LicenseRepr {
+  Uri(String),
+  Object {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>
+  }
+}
+
+impl From<LicenseRepr> for License {
+  fn from(repr: LicenseRepr) -> Self {
+    match repr {
+      LicenseRepr::Uri(id) => License{ id, extra: Map::new() },
+      LicenseRepr::Object{ id, extra } => License{ id, extra }
+    }
+  }
+}
+
+impl From<License> for LicenseRepr {
+  fn from(license: License) -> Self {
+    match license.extra.is_empty() {
+      true => LicenseRepr::Uri(license.id),
+      false => LicenseRepr::Object{ id: license.id, extra: license.extra }
+    }
+  }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Service {
+  #[serde(rename = "@context", default, skip_serializing_if = "String::is_empty")]
+  pub context: String,
+  #[serde(rename = "@id")]
+  pub id: String,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub profile: String,
+}
+
+/// A single entry of the 2.x profile, which may be a bare compliance level 
+/// URI, an array of URIs and objects, or a mix of both.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileEntry {
+  Uri(String),
+  Object(Profile)
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub formats: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub qualities: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub supports: Vec<String>,
+  #[serde(rename = "maxWidth", default, skip_serializing_if = "Option::is_none")]
+  pub max_width: Option<usize>,
+  #[serde(rename = "maxHeight", default, skip_serializing_if = "Option::is_none")]
+  pub max_height: Option<usize>,
+  #[serde(rename = "maxArea", default, skip_serializing_if = "Option::is_none")]
+  pub max_area: Option<u64>,
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
+}
+
+/// An Image API 3.0 information document, any properties not modelled here 
+/// are kept in `extra` and written back out when serializing.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InfoV3 {
+  #[serde(rename = "@context")]
+  pub context: Value,
+  pub id: String,
+  #[serde(rename = "type")]
+  pub service_type: String,
+  pub protocol: String,
+  pub profile: String,
+  pub width: usize,
+  pub height: usize,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub max_width: Option<usize>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub max_height: Option<usize>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub max_area: Option<u64>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub sizes: Vec<InfoSize>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub tiles: Vec<Tile>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub preferred_formats: Vec<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub rights: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub extra_qualities: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub extra_formats: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub extra_features: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub part_of: Vec<LinkedResource>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub see_also: Vec<LinkedResource>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub service: Vec<InfoService>,
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
+}
+
+/// A reference to another resource as used by the 3.0 partOf and seeAlso properties
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkedResource {
+  pub id: String,
+  #[serde(rename = "type")]
+  pub resource_type: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub label: Option<Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub format: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub profile: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_version() {
+    let v2 = r#"{"@context": "http://iiif.io/api/image/2/context.json", "@id": "https://example.org/iiif/1",
+      "protocol": "http://iiif.io/api/image", "width": 6000, "height": 4000,
+      "profile": ["http://iiif.io/api/image/2/level2.json", {"formats": ["webp"], "qualities": [], "supports": []}]}"#;
+    let v3 = r#"{"@context": "http://iiif.io/api/image/3/context.json", "id": "https://example.org/iiif/1",
+      "type": "ImageService3", "protocol": "http://iiif.io/api/image", "profile": "level2",
+      "width": 6000, "height": 4000, "maxWidth": 3000, "extraFormats": ["webp"], "rights": "http://rightsstatements.org/vocab/NoC-NC/1.0/"}"#;
+    let v2: Info = serde_json::from_str(v2).unwrap();
+    let v3: Info = serde_json::from_str(v3).unwrap();
+    assert_eq!(Version::V2, v2.version());
+    assert_eq!(Version::V3, v3.version());
+    assert_eq!(v2.formats(), v3.formats());
+    assert_eq!("https://example.org/iiif/1", v3.id());
+  }
+
+  #[test]
+  fn tolerates_optional_fields() {
+    let json = r#"{"@id": "https://example.org/iiif/1", "width": 100, "height": 200,
+      "attribution": "Provided by Example", "license": "https://creativecommons.org/licenses/by/4.0/",
+      "logo": "https://example.org/logo.png", "service": {"@context": "http://iiif.io/api/annex/services/physdim/1/context.json"},
+      "profile": "http://iiif.io/api/image/2/level0.json", "navDate": "1900-01-01T00:00:00Z"}"#;
+    let info: Info = serde_json::from_str(json).unwrap();
+    let v2 = match &info {
+      Info::V2(v2) => v2,
+      Info::V3(_) => panic!("Expected a 2.x document")
+    };
+    assert_eq!(None, v2.protocol);
+    assert_eq!(Attribution{ value: "Provided by Example".into(), language: None }, v2.attribution[0]);
+    assert_eq!("https://example.org/logo.png", v2.logo[0].id);
+    assert!(matches!(&v2.service[0], InfoService::PhysicalDimensions(p) if p.physical_scale.is_none()));
+    assert_eq!(Some(&Value::from("1900-01-01T00:00:00Z")), v2.extra.get("navDate"));
+
+    let reserialized = serde_json::to_value(&info).unwrap();
+    assert_eq!(Value::from("1900-01-01T00:00:00Z"), reserialized["navDate"]);
+    assert_eq!(info, serde_json::from_value(reserialized).unwrap());
+  }
+
+  #[test]
+  fn preserves_unknown_v3_fields() {
+    let json = r#"{"@context": ["http://iiif.io/api/extension/navplace/context.json", "http://iiif.io/api/image/3/context.json"],
+      "id": "https://example.org/iiif/1", "type": "ImageService3", "protocol": "http://iiif.io/api/image",
+      "profile": "level0", "width": 100, "height": 200, "navPlace": {"type": "FeatureCollection", "features": []}}"#;
+    let info: Info = serde_json::from_str(json).unwrap();
+    assert_eq!(Version::V3, info.version());
+    let reserialized: Value = serde_json::to_value(&info).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(json).unwrap(), reserialized);
+  }
+
+  #[test]
+  fn preserves_license_objects_and_unknown_v2_fields() {
+    let json = r#"{"@id": "https://example.org/iiif/1", "width": 100, "height": 200,
+      "license": [{"@id": "https://creativecommons.org/licenses/by/4.0/", "label": "CC BY"}, "https://example.org/terms"],
+      "tiles": [{"width": 256, "scaleFactors": [1, 2], "overlap": 1}],
+      "profile": ["http://iiif.io/api/image/2/level1.json", {"formats": ["webp"], "x-colorspaces": ["srgb"]}]}"#;
+    let info: Info = serde_json::from_str(json).unwrap();
+    let v2 = match &info {
+      Info::V2(v2) => v2,
+      Info::V3(_) => panic!("Expected a 2.x document")
+    };
+    assert_eq!("https://creativecommons.org/licenses/by/4.0/", v2.license[0].id);
+    assert_eq!("https://example.org/terms", v2.license[1].id);
+    assert_eq!(Some(&Value::from(1)), v2.tiles[0].extra.get("overlap"));
+    let reserialized: Value = serde_json::to_value(&info).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(json).unwrap(), reserialized);
+  }
+}
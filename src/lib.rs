@@ -2,15 +2,49 @@ mod url;
 mod utils;
 mod api;
 mod requests;
+mod session;
+mod retry;
+mod ratelimit;
+mod cache;
+mod download;
+mod progress;
+mod media;
+mod headers;
+mod resolve;
 mod responses;
+mod info;
+mod services;
+mod validation;
+mod compliance;
+mod dimensions;
+mod canonical;
+mod tiles;
+mod batch;
+#[cfg(feature = "stitch")]
+mod stitch;
 mod parameters;
-mod errors;
+pub mod errors;
 
 use serde::{Serialize, Deserialize};
 pub use api::*;
-pub use requests::*;
 pub use responses::*;
+pub use session::*;
+pub use retry::*;
+pub use ratelimit::*;
+pub use cache::{DiskCache, CacheStats};
+pub use download::*;
+pub use progress::*;
+pub use headers::*;
+pub use info::*;
+pub use services::*;
+pub use validation::*;
+pub use compliance::*;
+pub use dimensions::*;
+pub use tiles::*;
+pub use batch::*;
 pub use parameters::*;
+pub use errors::{Error, Parameter, ParseError};
 pub use bytes::Bytes;
 pub use reqwest::{Client, Url};
+pub use reqwest::header::HeaderMap;
 
@@ -0,0 +1,120 @@
+//! Media types of the image formats and checking responses against them
+use super::*;
+
+impl Format {
+  /// The IANA media type the Image API pairs with the format
+  pub fn mime_type(&self) -> &'static str {
+    match self {
+      Format::Jpg => "image/jpeg",
+      Format::Tif => "image/tiff",
+      Format::Png => "image/png",
+      Format::Gif => "image/gif",
+      Format::Jp2 => "image/jp2",
+      Format::Pdf => "application/pdf",
+      Format::Webp => "image/webp"
+    }
+  }
+
+  /// The format for a media type such as a Content-Type header, ignoring 
+  /// case and any parameters like charset
+  pub fn from_mime(mime: &str) -> Option<Format> {
+    let essence = mime.split(';').next().unwrap_or_default().trim().to_lowercase();
+    match essence.as_str() {
+      "image/jpeg" | "image/jpg" | "image/pjpeg" => Some(Format::Jpg),
+      "image/tiff" | "image/tif" => Some(Format::Tif),
+      "image/png" => Some(Format::Png),
+      "image/gif" => Some(Format::Gif),
+      "image/jp2" | "image/jpx" | "image/jpm" | "image/j2k" => Some(Format::Jp2),
+      "application/pdf" => Some(Format::Pdf),
+      "image/webp" => Some(Format::Webp),
+      _ => None
+    }
+  }
+
+  /// Detects the format from the magic bytes at the start of a body
+  pub fn sniff(bytes: &[u8]) -> Option<Format> {
+    match bytes {
+      [0xFF, 0xD8, 0xFF, ..] => Some(Format::Jpg),
+      [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', ..] => Some(Format::Png),
+      [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(Format::Gif),
+      [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => Some(Format::Tif),
+      [0x00, 0x00, 0x00, 0x0C, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A, ..] => Some(Format::Jp2),
+      [0xFF, 0x4F, 0xFF, 0x51, ..] => Some(Format::Jp2),
+      [b'%', b'P', b'D', b'F', b'-', ..] => Some(Format::Pdf),
+      [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Format::Webp),
+      _ => None
+    }
+  }
+}
+
+// Enough of the start of a body to cover every magic number sniffed
+pub(crate) const SNIFF_LEN: usize = 16;
+
+/// Checks a response body against the requested format, returning the 
+/// format it was detected as. The magic bytes take precedence over the 
+/// Content-Type, a body that matches neither is only rejected when its 
+/// Content-Type names another format or a text document such as an HTML 
+/// error page.
+pub(crate) fn verify_format(url: &Url, expected: Format, content_type: Option<&str>, body: &[u8]) -> Result<Option<Format>, Error> {
+  let declared = content_type.and_then(Format::from_mime);
+  let detected = Format::sniff(body).or(declared);
+  let textual = content_type.is_some_and(|mime| {
+    let mime = mime.to_lowercase();
+    mime.starts_with("text/") || mime.contains("html") || mime.contains("json") || mime.contains("xml")
+  });
+  match detected {
+    Some(found) if found != expected => Err(mismatch(url, expected, Some(found), content_type)),
+    None if textual => Err(mismatch(url, expected, None, content_type)),
+    _ => Ok(detected)
+  }
+}
+
+fn mismatch(url: &Url, expected: Format, found: Option<Format>, content_type: Option<&str>) -> Error {
+  Error::FormatMismatch {
+    url: url.clone(),
+    expected,
+    found,
+    content_type: content_type.map(|mime| mime.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn mime_round_trip() {
+    for format in [Format::Jpg, Format::Tif, Format::Png, Format::Gif, Format::Jp2, Format::Pdf, Format::Webp] {
+      assert_eq!(Some(format), Format::from_mime(format.mime_type()));
+    }
+    assert_eq!(Some(Format::Jpg), Format::from_mime("Image/JPEG; charset=binary"));
+    assert_eq!(None, Format::from_mime("text/html"));
+  }
+
+  #[test]
+  fn sniffs_magic_bytes() {
+    assert_eq!(Some(Format::Jpg), Format::sniff(&[0xFF, 0xD8, 0xFF, 0xE0]));
+    assert_eq!(Some(Format::Png), Format::sniff(b"\x89PNG\r\n\x1a\n...."));
+    assert_eq!(Some(Format::Gif), Format::sniff(b"GIF89a"));
+    assert_eq!(Some(Format::Tif), Format::sniff(b"MM\x00\x2a"));
+    assert_eq!(Some(Format::Jp2), Format::sniff(b"\x00\x00\x00\x0cjP  \r\n\x87\n"));
+    assert_eq!(Some(Format::Pdf), Format::sniff(b"%PDF-1.7"));
+    assert_eq!(Some(Format::Webp), Format::sniff(b"RIFF\x10\x00\x00\x00WEBPVP8 "));
+    assert_eq!(None, Format::sniff(b"<!DOCTYPE html>"));
+  }
+
+  #[test]
+  fn rejects_mismatches() {
+    let url = Url::parse("https://example.org/iiif/abc/full/max/0/default.jpg").unwrap();
+    let jpeg = [0xFF, 0xD8, 0xFF, 0xE0];
+    assert_eq!(Some(Format::Jpg), verify_format(&url, Format::Jpg, Some("image/jpeg"), &jpeg).unwrap());
+    assert_eq!(Some(Format::Jpg), verify_format(&url, Format::Jpg, Some("application/octet-stream"), &jpeg).unwrap());
+    assert_eq!(None, verify_format(&url, Format::Jpg, None, b"????").unwrap());
+    assert!(verify_format(&url, Format::Png, Some("image/png"), &jpeg).is_err());
+    assert!(verify_format(&url, Format::Jpg, Some("image/png"), b"????").is_err());
+    match verify_format(&url, Format::Jpg, Some("text/html; charset=utf-8"), b"<html>") {
+      Err(Error::FormatMismatch { expected: Format::Jpg, found: None, content_type: Some(_), .. }) => {}
+      other => panic!("expected a mismatch, got {:?}", other)
+    }
+  }
+}
@@ -1,5 +1,8 @@
 //! Contains objects representing image parameters
 use crate::utils::*;
+use crate::errors::{Parameter, ParseError};
+use std::fmt;
+use std::str::FromStr;
 
 /// The region parameter defines the rectangular portion of the 
 /// full image to be returned. Region can be specified by pixel coordinates, 
@@ -13,9 +16,10 @@ use crate::utils::*;
 /// | Full | The complete image is returned, without any cropping. |
 /// | Square | The region is defined as an area where the width and height are both equal to the length of the shorter dimension of the complete image. The region may be positioned anywhere in the longer dimension of the image content at the server’s discretion, and centered is often a reasonable default. |
 /// | Absolute(x,y,w,h) | The region of the full image to be returned is specified in terms of absolute pixel values. The value of x represents the number of pixels from the 0 position on the horizontal axis. The value of y represents the number of pixels from the 0 position on the vertical axis. Thus the x,y position 0,0 is the upper left-most pixel of the image. w represents the width of the region and h represents the height of the region in pixels. |
-/// | Percentage(x,y,w,h) | The region to be returned is specified as a sequence of percentages of the full image’s dimensions, as reported in the image information document. Thus, x represents the number of pixels from the 0 position on the horizontal axis, calculated as a percentage of the reported width. w represents the width of the region, also calculated as a percentage of the reported width. The same applies to y and h respectively. These may be floating point numbers. |#[derive(Debug, Clone, PartialEq)]
-#[derive(Debug, Clone, PartialEq)]
+/// | Percentage(x,y,w,h) | The region to be returned is specified as a sequence of percentages of the full image’s dimensions, as reported in the image information document. Thus, x represents the number of pixels from the 0 position on the horizontal axis, calculated as a percentage of the reported width. w represents the width of the region, also calculated as a percentage of the reported width. The same applies to y and h respectively. These may be floating point numbers. |
+#[derive(Default, Debug, Clone, PartialEq)]
 pub enum Region {
+  #[default]
   Full,
   Square,
   Abs(Absolute),
@@ -51,15 +55,53 @@ pub struct Percentage {
 /// | Pct(n) | The width and height of the returned image is scaled to n% of the width and height of the extracted region. The aspect ratio of the returned image is the same as that of the extracted region. |
 /// | WH(w,h) | The width and height of the returned image are exactly w and h. The aspect ratio of the returned image may be different than the extracted region, resulting in a distorted image. |
 /// | LtWH(w,h) | The image content is scaled for the best fit such that the resulting width and height are less than or equal to the requested width and height. The exact scaling may be determined by the service provider, based on characteristics including image quality and system performance. The dimensions of the returned image content are calculated to maintain the aspect ratio of the extracted region. |
-#[derive(Debug, Clone, PartialEq)]
+/// 
+/// Version 3.0 adds upscaling forms prefixed with `^`, these allow the returned image to be larger than the extracted region and require the server to list `sizeUpscaling` in its supported features.
+/// 
+/// | Form | Description |
+/// |-------|------------------------------------------------------------------------------|
+/// | UpMax | `^max`, the region is returned at the maximum size available, which may be larger than the region. |
+/// | UpW(w) | `^w,`, as W(w) but the width may exceed the width of the region. |
+/// | UpH(h) | `^,h`, as H(h) but the height may exceed the height of the region. |
+/// | UpPct(n) | `^pct:n`, as Pct(n) but n may be greater than 100. |
+/// | UpWH(w,h) | `^w,h`, as WH(w,h) but either dimension may exceed the region. |
+/// | UpLtWH(w,h) | `^!w,h`, as LtWH(w,h) but the best fit may be larger than the region. |
+#[derive(Default, Debug, Clone, PartialEq)]
 pub enum Size {
+  #[default]
   Full,
   Max,
   W(usize),
   H(usize),
   Pct(u16),
   WH(usize,usize),
-  LtWH(usize, usize)
+  LtWH(usize, usize),
+  UpMax,
+  UpW(usize),
+  UpH(usize),
+  UpPct(u16),
+  UpWH(usize, usize),
+  UpLtWH(usize, usize)
+}
+
+impl Size {
+  /// Whether the size is one of the 3.0 upscaling forms
+  pub fn is_upscaled(&self) -> bool {
+    matches!(self, Size::UpMax | Size::UpW(_) | Size::UpH(_) | Size::UpPct(_) | Size::UpWH(..) | Size::UpLtWH(..))
+  }
+
+  // The upscaling equivalent, full has none as it was removed in 3.0
+  fn upscaled(self) -> Option<Size> {
+    match self {
+      Size::Max => Some(Size::UpMax),
+      Size::W(w) => Some(Size::UpW(w)),
+      Size::H(h) => Some(Size::UpH(h)),
+      Size::Pct(n) => Some(Size::UpPct(n)),
+      Size::WH(w, h) => Some(Size::UpWH(w, h)),
+      Size::LtWH(w, h) => Some(Size::UpLtWH(w, h)),
+      _ => None
+    }
+  }
 }
 
 /// The rotation parameter specifies mirroring and rotation. A leading exclamation mark (“!”) indicates that the image should be mirrored by reflection on the vertical axis before any rotation is applied. The numerical value represents the number of degrees of clockwise rotation, and may be any floating point number from 0 to 360.
@@ -77,9 +119,6 @@ pub enum Rotation {
 }
 
 /// The quality parameter determines whether the image is delivered in color, grayscale or black and white.
-
-
-
 /// 
 /// The default is Quality::ServerDefault
 /// 
@@ -89,8 +128,9 @@ pub enum Rotation {
 /// | gray | The image is returned in grayscale, where each pixel is black, white or any shade of gray in between. |
 /// | bitonal | The image returned is bitonal, where each pixel is either black or white. |
 /// | default | The image is returned using the server’s default quality (e.g. color, gray or bitonal) for the image. |
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub enum Quality {
+  #[default]
   ServerDefault,
   Color,
   Gray,
@@ -102,8 +142,9 @@ pub enum Quality {
 /// The default is Format::Jpg
 /// 
 /// A format value that is unsupported should result in a 400 status code.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub enum Format {
+  #[default]
   Jpg,
   Tif,
   Png,
@@ -113,104 +154,223 @@ pub enum Format {
   Webp
 }
 
-impl ToString for Region {
-  fn to_string(&self) -> String {
+impl fmt::Display for Region {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Region::Full => "full".into(),
-      Region::Square => "square".into(),
-      Region::Abs(a) => a.to_string(),
-      Region::Pct(p) => p.to_string()
+      Region::Full => write!(f, "full"),
+      Region::Square => write!(f, "square"),
+      Region::Abs(a) => write!(f, "{}", a),
+      Region::Pct(p) => write!(f, "{}", p)
     }
   }
 }
 
-impl ToString for Absolute {
-  fn to_string(&self) -> String {
-    join_coords(self.x, self.y, self.w, self.h)
+impl fmt::Display for Absolute {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", join_coords(self.x, self.y, self.w, self.h))
   }
 }
 
-impl ToString for Percentage {
-  fn to_string(&self) -> String {
-    let floats =format_floats(vec![self.x, self.y, self.w, self.h]);
+impl fmt::Display for Percentage {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let floats = format_floats(vec![self.x, self.y, self.w, self.h]);
     let coords = join_coords(&floats[0], &floats[1], &floats[2], &floats[3]);
-    ["pct:".to_string(), coords].join("")
+    write!(f, "pct:{}", coords)
   }
 }
 
-impl ToString for Size {
-  fn to_string(&self) -> String {
+impl fmt::Display for Size {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Size::Full => "full".into(),
-      Size::Max => "max".into(),
-      Size::W(w) => format!("{},", w),
-      Size::H(h) => format!(",{}", h),
-      Size::Pct(n) => format!("pct:{}", n),
-      Size::WH(w,h) => format!("{},{}", w, h),
-      Size::LtWH(w,h) => format!("!{},{}", w, h)
+      Size::Full => write!(f, "full"),
+      Size::Max => write!(f, "max"),
+      Size::W(w) => write!(f, "{},", w),
+      Size::H(h) => write!(f, ",{}", h),
+      Size::Pct(n) => write!(f, "pct:{}", n),
+      Size::WH(w,h) => write!(f, "{},{}", w, h),
+      Size::LtWH(w,h) => write!(f, "!{},{}", w, h),
+      Size::UpMax => write!(f, "^max"),
+      Size::UpW(w) => write!(f, "^{},", w),
+      Size::UpH(h) => write!(f, "^,{}", h),
+      Size::UpPct(n) => write!(f, "^pct:{}", n),
+      Size::UpWH(w,h) => write!(f, "^{},{}", w, h),
+      Size::UpLtWH(w,h) => write!(f, "^!{},{}", w, h)
     }
   }
 }
 
-impl ToString for Rotation {
+impl fmt::Display for Rotation {
   // limit float values to 3 decimal places
-  fn to_string(&self) -> String {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Rotation::Normal(n) => format!("{:.5}", n.to_string()),
-      Rotation::Mirror(n) =>  format!("!{:.5}", n.to_string())
+      Rotation::Normal(n) => write!(f, "{}", format_float(*n)),
+      Rotation::Mirror(n) => write!(f, "!{}", format_float(*n))
     }
   }
 }
 
-impl ToString for Quality {
-  fn to_string(&self) -> String {
+impl fmt::Display for Quality {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Quality::ServerDefault => "default".into(),
-      Quality::Color => "color".into(),
-      Quality::Bitonal => "bitonal".into(),
-      Quality::Gray => "gray".into()
+      Quality::ServerDefault => write!(f, "default"),
+      Quality::Color => write!(f, "color"),
+      Quality::Bitonal => write!(f, "bitonal"),
+      Quality::Gray => write!(f, "gray")
     }
   }
 }
 
-impl ToString for Format {
-  fn to_string(&self) -> String {
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Format::Jpg => "jpg".into(),
-      Format::Tif => "tif".into(),
-      Format::Png => "png".into(),
-      Format::Gif => "gif".into(),
-      Format::Jp2 => "jp2".into(),
-      Format::Pdf => "pdf".into(),
-      Format::Webp => "webp".into()
+      Format::Jpg => write!(f, "jpg"),
+      Format::Tif => write!(f, "tif"),
+      Format::Png => write!(f, "png"),
+      Format::Gif => write!(f, "gif"),
+      Format::Jp2 => write!(f, "jp2"),
+      Format::Pdf => write!(f, "pdf"),
+      Format::Webp => write!(f, "webp")
     }
   }
 }
 
-// Default image settings
-impl Default for Region {
-  fn default() -> Self { Region::Full }
+// Parsing of the IIIF parameter syntax, the inverse of Display
+impl FromStr for Region {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "full" => Ok(Region::Full),
+      "square" => Ok(Region::Square),
+      _ if s.starts_with("pct:") => s.parse().map(Region::Pct),
+      _ if s.contains(',') => s.parse().map(Region::Abs),
+      _ => Err(ParseError::new(Parameter::Region, s, "expected full, square, x,y,w,h or pct:x,y,w,h"))
+    }
+  }
 }
 
-impl Default for Size {
-  fn default() -> Self { Size::Full }
+impl FromStr for Absolute {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let [x, y, w, h] = split_coords(s).map_err(|e| ParseError::new(Parameter::Region, s, &e))?;
+    Ok(Absolute{ x, y, w, h })
+  }
 }
 
-impl Default for Rotation {
-  fn default() -> Self { Rotation::Normal(0.0) }
+impl FromStr for Percentage {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let err = |details: &str| ParseError::new(Parameter::Region, s, details);
+    let coords = s.strip_prefix("pct:").ok_or_else(|| err("expected the pct: prefix"))?;
+    let [x, y, w, h]: [f32; 4] = split_coords(coords).map_err(|e| err(&e))?;
+    if [x, y, w, h].iter().any(|n| !n.is_finite() || *n < 0.0) {
+      return Err(err("percentages must be positive numbers"));
+    }
+    Ok(Percentage{ x, y, w, h })
+  }
 }
 
-impl Default for Quality {
-  fn default() -> Self { Quality::ServerDefault }
+impl FromStr for Size {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let err = |details: &str| ParseError::new(Parameter::Size, s, details);
+    if let Some(size) = s.strip_prefix('^') {
+      return match size.parse::<Size>() {
+        Ok(size) => size.upscaled().ok_or_else(|| err("^ must be followed by max, w,, ,h, pct:n, w,h or !w,h")),
+        Err(e) => Err(err(&e.details))
+      };
+    }
+    match s {
+      "full" => return Ok(Size::Full),
+      "max" => return Ok(Size::Max),
+      _ => {}
+    }
+    if let Some(n) = s.strip_prefix("pct:") {
+      return n.parse()
+              .map(Size::Pct)
+              .map_err(|_| err(&format!("{:?} is not a whole percentage", n)));
+    }
+    let (best_fit, dims) = match s.strip_prefix('!') {
+      Some(dims) => (true, dims),
+      None => (false, s)
+    };
+    let (w, h) = dims.split_once(',')
+                     .ok_or_else(|| err("expected full, max, w,, ,h, pct:n, w,h or !w,h"))?;
+    let w = parse_dimension(w).map_err(|e| err(&e))?;
+    let h = parse_dimension(h).map_err(|e| err(&e))?;
+    match (w, h) {
+      (Some(w), Some(h)) if best_fit => Ok(Size::LtWH(w, h)),
+      (Some(w), Some(h)) => Ok(Size::WH(w, h)),
+      _ if best_fit => Err(err("!w,h requires both a width and a height")),
+      (Some(w), None) => Ok(Size::W(w)),
+      (None, Some(h)) => Ok(Size::H(h)),
+      (None, None) => Err(err("expected a width, a height or both"))
+    }
+  }
 }
 
-impl Default for Format {
-  fn default() -> Self { Format::Jpg }
+impl FromStr for Rotation {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let err = |details: &str| ParseError::new(Parameter::Rotation, s, details);
+    let (mirror, degrees) = match s.strip_prefix('!') {
+      Some(n) => (true, n),
+      None => (false, s)
+    };
+    let n: f32 = degrees.parse().map_err(|_| err(&format!("{:?} is not a number", degrees)))?;
+    if !(0.0..=360.0).contains(&n) {
+      return Err(err("degrees must be between 0 and 360"));
+    }
+    match mirror {
+      true => Ok(Rotation::Mirror(n)),
+      false => Ok(Rotation::Normal(n))
+    }
+  }
+}
+
+impl FromStr for Quality {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "default" => Ok(Quality::ServerDefault),
+      "color" => Ok(Quality::Color),
+      "gray" => Ok(Quality::Gray),
+      "bitonal" => Ok(Quality::Bitonal),
+      _ => Err(ParseError::new(Parameter::Quality, s, "expected default, color, gray or bitonal"))
+    }
+  }
+}
+
+impl FromStr for Format {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "jpg" => Ok(Format::Jpg),
+      "tif" => Ok(Format::Tif),
+      "png" => Ok(Format::Png),
+      "gif" => Ok(Format::Gif),
+      "jp2" => Ok(Format::Jp2),
+      "pdf" => Ok(Format::Pdf),
+      "webp" => Ok(Format::Webp),
+      _ => Err(ParseError::new(Parameter::Format, s, "expected jpg, tif, png, gif, jp2, pdf or webp"))
+    }
+  }
+}
+
+impl Default for Rotation {
+  fn default() -> Self { Rotation::Normal(0.0) }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use proptest::prelude::*;
   #[test]
   fn absolute_to_string() {
     let abs = Absolute {
@@ -229,6 +389,132 @@ mod tests {
       w: 3.03, 
       h: 4.0
     };
-    assert_eq!("pct:1.234,2,3.03,4", pct.to_string());
+    assert_eq!("pct:1.235,2,3.03,4", pct.to_string());
+  }
+  #[test]
+  fn rotation_to_string() {
+    assert_eq!("12.345", Rotation::Normal(12.345).to_string());
+    assert_eq!("!90", Rotation::Mirror(90.0).to_string());
+    assert_eq!("0", Rotation::Normal(0.00001).to_string());
+    assert_eq!("359.999", Rotation::Normal(359.9994).to_string());
+  }
+  #[test]
+  fn size_from_str() {
+    assert_eq!(Size::W(500), "500,".parse().unwrap());
+    assert_eq!(Size::H(150), ",150".parse().unwrap());
+    assert_eq!(Size::LtWH(200, 300), "!200,300".parse().unwrap());
+    assert!("!200,".parse::<Size>().is_err());
+    assert_eq!(Size::UpLtWH(200, 300), "^!200,300".parse().unwrap());
+    assert_eq!(Size::UpPct(150), "^pct:150".parse().unwrap());
+    assert!("^full".parse::<Size>().is_err());
+    assert!("^^max".parse::<Size>().is_err());
+  }
+  #[test]
+  fn region_from_str() {
+    let pct = Percentage { x: 10.0, y: 20.0, w: 30.5, h: 40.0 };
+    assert_eq!(Region::Pct(pct), "pct:10,20,30.5,40".parse().unwrap());
+    assert_eq!(Region::Abs(Absolute { x: 1, y: 2, w: 3, h: 4 }), "1,2,3,4".parse().unwrap());
+    assert!("1,2,3".parse::<Region>().is_err());
+  }
+  #[test]
+  fn error_describes_token() {
+    let err = "pct:10,x,30,40".parse::<Region>().unwrap_err();
+    assert_eq!(Parameter::Region, err.parameter);
+    assert!(err.details.contains("\"x\""));
+    let err = "!400".parse::<Rotation>().unwrap_err();
+    assert_eq!("degrees must be between 0 and 360", err.details);
+  }
+
+  fn region() -> impl Strategy<Value = Region> {
+    let pct = 0f32..=100.0;
+    prop_oneof![
+      Just(Region::Full),
+      Just(Region::Square),
+      (any::<usize>(), any::<usize>(), any::<usize>(), any::<usize>())
+        .prop_map(|(x, y, w, h)| Region::Abs(Absolute{ x, y, w, h })),
+      (pct.clone(), pct.clone(), pct.clone(), pct)
+        .prop_map(|(x, y, w, h)| Region::Pct(Percentage{ x, y, w, h })),
+    ]
+  }
+
+  fn size() -> impl Strategy<Value = Size> {
+    prop_oneof![
+      Just(Size::Full),
+      Just(Size::Max),
+      any::<usize>().prop_map(Size::W),
+      any::<usize>().prop_map(Size::H),
+      any::<u16>().prop_map(Size::Pct),
+      (any::<usize>(), any::<usize>()).prop_map(|(w, h)| Size::WH(w, h)),
+      (any::<usize>(), any::<usize>()).prop_map(|(w, h)| Size::LtWH(w, h)),
+      Just(Size::UpMax),
+      any::<usize>().prop_map(Size::UpW),
+      any::<usize>().prop_map(Size::UpH),
+      any::<u16>().prop_map(Size::UpPct),
+      (any::<usize>(), any::<usize>()).prop_map(|(w, h)| Size::UpWH(w, h)),
+      (any::<usize>(), any::<usize>()).prop_map(|(w, h)| Size::UpLtWH(w, h)),
+    ]
+  }
+
+  // Whether the written value is the original rounded to 3 decimal places
+  fn rounds_to(original: f32, parsed: f32) -> bool {
+    (original - parsed).abs() <= 0.0005 + original.abs() * f32::EPSILON
+  }
+
+  fn rotation() -> impl Strategy<Value = Rotation> {
+    let degrees = 0f32..=360.0;
+    prop_oneof![
+      degrees.clone().prop_map(Rotation::Normal),
+      degrees.prop_map(Rotation::Mirror),
+    ]
+  }
+
+  proptest! {
+    // Floats are written to 3 decimal places, so parsing only recovers them 
+    // to within rounding and writing them again must give the same string
+    #[test]
+    fn region_round_trip(region in region()) {
+      let parsed = region.to_string().parse::<Region>().unwrap();
+      prop_assert_eq!(region.to_string(), parsed.to_string());
+      match (&region, &parsed) {
+        (Region::Pct(a), Region::Pct(b)) => {
+          for (a, b) in [(a.x, b.x), (a.y, b.y), (a.w, b.w), (a.h, b.h)] {
+            prop_assert!(rounds_to(a, b));
+          }
+        },
+        _ => prop_assert_eq!(region, parsed)
+      }
+    }
+
+    #[test]
+    fn size_round_trip(size in size()) {
+      prop_assert_eq!(size.clone(), size.to_string().parse::<Size>().unwrap());
+    }
+
+    #[test]
+    fn rotation_round_trip(rotation in rotation()) {
+      let parsed = rotation.to_string().parse::<Rotation>().unwrap();
+      prop_assert_eq!(rotation.to_string(), parsed.to_string());
+      match (rotation, parsed) {
+        (Rotation::Normal(a), Rotation::Normal(b)) | (Rotation::Mirror(a), Rotation::Mirror(b)) => {
+          prop_assert!(rounds_to(a, b));
+        },
+        (a, b) => prop_assert_eq!(a, b)
+      }
+    }
+
+    #[test]
+    fn quality_round_trip(quality in prop_oneof![
+      Just(Quality::ServerDefault), Just(Quality::Color), Just(Quality::Gray), Just(Quality::Bitonal)
+    ]) {
+      prop_assert_eq!(quality.clone(), quality.to_string().parse::<Quality>().unwrap());
+    }
+
+    #[test]
+    fn format_round_trip(format in prop_oneof![
+      Just(Format::Jpg), Just(Format::Tif), Just(Format::Png), Just(Format::Gif),
+      Just(Format::Jp2), Just(Format::Pdf), Just(Format::Webp)
+    ]) {
+      prop_assert_eq!(format, format.to_string().parse::<Format>().unwrap());
+    }
   }
 }
\ No newline at end of file
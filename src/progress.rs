@@ -0,0 +1,39 @@
+//! Observing the progress of requests and downloads
+use super::*;
+
+/// Receives progress events for every request sent through a session, 
+/// including streaming and batch downloads, so progress bars and metrics 
+/// can be plugged in. Every method does nothing by default.
+///
+/// `started` and `received` are called again from zero when a request is 
+/// retried, and either `finished` or `failed` is called once at the end. A 
+/// response answered from the cache is started and finished without any 
+/// `received` events.
+///
+/// ```rust,ignore
+/// use iiif::*;
+///
+/// struct Log;
+///
+/// impl Progress for Log {
+///   fn received(&self, url: &Url, received: u64, total: Option<u64>) {
+///     println!("{}: {} of {:?} bytes", url, received, total);
+///   }
+/// }
+///
+/// let mut session = Session::new(&Client::new());
+/// session.progress(Log);
+/// ```
+pub trait Progress: Send + Sync {
+  /// The server responded, with the Content-Length if it sent one
+  fn started(&self, _url: &Url, _total: Option<u64>) {}
+
+  /// A chunk of the body arrived, with the total bytes received so far
+  fn received(&self, _url: &Url, _received: u64, _total: Option<u64>) {}
+
+  /// The request succeeded with a body of the given size
+  fn finished(&self, _url: &Url, _size: u64) {}
+
+  /// The request failed after any retries
+  fn failed(&self, _url: &Url, _error: &Error) {}
+}
@@ -0,0 +1,202 @@
+//! Throttling requests per host with a token bucket and a concurrency cap
+use super::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The rate and concurrency allowed against a single host. Requests are 
+/// spaced out to `requests_per_second` on average, up to `burst` requests 
+/// may be sent at once after a quiet period, and no more than 
+/// `max_concurrent` are ever in flight. A `requests_per_second` of zero or 
+/// less, or NaN, turns off the spacing and only caps concurrency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+  pub requests_per_second: f64,
+  pub burst: u32,
+  pub max_concurrent: usize
+}
+
+impl RateLimit {
+  /// Creates a limit with a burst of one, so requests are evenly spaced
+  pub fn new(requests_per_second: f64, max_concurrent: usize) -> RateLimit {
+    RateLimit {
+      requests_per_second,
+      burst: 1,
+      max_concurrent: max_concurrent.max(1)
+    }
+  }
+
+  /// Sets how many requests may be sent at once after a quiet period
+  pub fn burst(&mut self, burst: u32) {
+    self.burst = burst.max(1);
+  }
+}
+
+/// Throttles requests per host, each host gets its own token bucket and 
+/// concurrency cap. Clones share their buckets, so every task using a clone 
+/// of the same limiter, or of a session holding it, is throttled together.
+///
+/// ```rust,ignore
+/// use iiif::*;
+///
+/// // Two requests a second and four in flight for any host
+/// let mut limiter = RateLimiter::new(RateLimit::new(2.0, 4));
+/// // Apart from one with a stricter fair-use policy
+/// limiter.host("ids.lib.harvard.edu", RateLimit::new(1.0, 1));
+///
+/// let mut session = Session::new(&Client::new());
+/// session.rate_limit(limiter);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+  default: RateLimit,
+  hosts: HashMap<String, RateLimit>,
+  buckets: Arc<Mutex<HashMap<String, Bucket>>>
+}
+
+#[derive(Debug)]
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+  permits: Arc<Semaphore>
+}
+
+/// Held while a request is in flight, dropping it frees a concurrency slot
+/// for the host
+#[derive(Debug)]
+pub struct RatePermit {
+  _permit: OwnedSemaphorePermit
+}
+
+impl RateLimiter {
+  /// Creates a limiter applying the limit to every host
+  pub fn new(default: RateLimit) -> RateLimiter {
+    RateLimiter {
+      default,
+      hosts: HashMap::new(),
+      buckets: Arc::new(Mutex::new(HashMap::new()))
+    }
+  }
+
+  /// Applies a different limit to a host, such as `ids.lib.harvard.edu`.
+  /// The host's bucket is rebuilt so the limit takes effect immediately, 
+  /// requests already holding a permit keep it. Clones made before the 
+  /// call keep the limits they were cloned with.
+  pub fn host(&mut self, host: &str, limit: RateLimit) {
+    let host = host.to_lowercase();
+    self.buckets.lock().unwrap_or_else(|e| e.into_inner()).remove(&host);
+    self.hosts.insert(host, limit);
+  }
+
+  /// The limit that applies to a host
+  pub fn limit(&self, host: &str) -> &RateLimit {
+    self.hosts.get(&host.to_lowercase()).unwrap_or(&self.default)
+  }
+
+  /// Waits for a concurrency slot and then a token for the url's host, 
+  /// returning a permit to hold until the response has been read
+  pub async fn acquire(&self, url: &Url) -> RatePermit {
+    let host = url.host_str().unwrap_or_default().to_lowercase();
+    let limit = self.limit(&host).clone();
+    let permits = self.with_bucket(&host, &limit, |bucket| bucket.permits.clone());
+    let permit = permits.acquire_owned()
+                        .await
+                        .expect("rate limiter semaphores are never closed");
+    if limit.requests_per_second.is_nan() || limit.requests_per_second <= 0.0 {
+      return RatePermit { _permit: permit }
+    }
+
+    // Take a token even if it leaves the bucket in debt, then wait out the 
+    // debt, this queues concurrent callers in order without polling
+    let wait = self.with_bucket(&host, &limit, |bucket| {
+      let now = Instant::now();
+      let refill = now.duration_since(bucket.updated).as_secs_f64() * limit.requests_per_second;
+      bucket.tokens = (bucket.tokens + refill).min(limit.burst as f64) - 1.0;
+      bucket.updated = now;
+      match bucket.tokens < 0.0 {
+        true => Duration::from_secs_f64(-bucket.tokens / limit.requests_per_second),
+        false => Duration::ZERO
+      }
+    });
+    if !wait.is_zero() {
+      tokio::time::sleep(wait).await;
+    }
+    RatePermit { _permit: permit }
+  }
+
+  fn with_bucket<T>(&self, host: &str, limit: &RateLimit, f: impl FnOnce(&mut Bucket) -> T) -> T {
+    let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+    let bucket = buckets.entry(host.into()).or_insert_with(|| Bucket {
+      tokens: limit.burst as f64,
+      updated: Instant::now(),
+      permits: Arc::new(Semaphore::new(limit.max_concurrent.max(1)))
+    });
+    f(bucket)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn url(s: &str) -> Url {
+    Url::parse(s).unwrap()
+  }
+
+  #[tokio::test]
+  async fn spaces_requests_per_host() {
+    let limiter = RateLimiter::new(RateLimit::new(20.0, 8));
+    let start = Instant::now();
+    for _ in 0..5 {
+      limiter.acquire(&url("https://a.example.org/iiif/1/info.json")).await;
+    }
+    // The first request uses the burst token, the other four wait 50ms each
+    assert!(start.elapsed() >= Duration::from_millis(190));
+
+    // Another host has its own bucket
+    let start = Instant::now();
+    limiter.clone().acquire(&url("https://b.example.org/iiif/1/info.json")).await;
+    assert!(start.elapsed() < Duration::from_millis(40));
+  }
+
+  #[tokio::test]
+  async fn caps_concurrent_requests() {
+    let mut limiter = RateLimiter::new(RateLimit::new(1000.0, 8));
+    let mut limit = RateLimit::new(1000.0, 1);
+    limit.burst(10);
+    limiter.host("A.example.org", limit);
+    let a = url("https://a.example.org/iiif/1/info.json");
+
+    let held = limiter.acquire(&a).await;
+    let blocked = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(&a)).await;
+    assert!(blocked.is_err());
+    drop(held);
+    let freed = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(&a)).await;
+    assert!(freed.is_ok());
+  }
+
+  #[tokio::test]
+  async fn host_limit_replaces_bucket() {
+    let mut limiter = RateLimiter::new(RateLimit::new(1000.0, 1));
+    let a = url("https://a.example.org/iiif/1/info.json");
+    let held = limiter.acquire(&a).await;
+    limiter.host("a.example.org", RateLimit::new(1000.0, 2));
+    assert_eq!(2, limiter.limit("a.example.org").max_concurrent);
+    let second = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(&a)).await;
+    assert!(second.is_ok());
+    drop(held);
+  }
+
+  #[tokio::test]
+  async fn zero_rate_only_caps_concurrency() {
+    for rate in [0.0, -1.0, f64::NAN] {
+      let limiter = RateLimiter::new(RateLimit::new(rate, 8));
+      let start = Instant::now();
+      for _ in 0..5 {
+        limiter.acquire(&url("https://a.example.org/iiif/1/info.json")).await;
+      }
+      assert!(start.elapsed() < Duration::from_millis(40));
+    }
+  }
+}
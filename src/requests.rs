@@ -1,58 +1,199 @@
 //! Contains the various request functions for the Image struct
 use super::*;
-use std::error::Error;
 use crate::errors::*;
+use crate::media::verify_format;
+use crate::retry::parse_retry_after;
+use bytes::BytesMut;
+use crate::cache::Metadata;
+use reqwest::header::{self, HeaderMap};
 
 
 impl Image {
   /// Makes an asynchronous request with the current parameters using a reusable 
   /// http client and returns a response struct containing a byte array of the 
   /// image. This method is recommended for anything beyond making a few 
-  /// requests as it can take advantage of keep-alive pooling.
-  pub async fn request(self, client: &Client) -> Result<Response, Box<dyn Error>> {
-    let parts = self.build_parts();
-    let url = self.build_uri(parts);
-    let response = client.get(url.clone())
-                          .send()
-                          .await?;
-    let status_code = response.status().as_u16();
-    let image = response.bytes().await?;
-    match status_code {
-      200..=299 =>  Ok(Response{status_code, url, image}),
-      _ => Err(Box::new(ResponseError::new(status_code)))
-    }
+  /// requests as it can take advantage of keep-alive pooling. A single attempt 
+  /// is made, see `request_with` to retry transient failures.
+  pub async fn request(self, client: &Client) -> Result<Response, Error> {
+    let mut session = Session::new(client);
+    session.retry(RetryPolicy::none());
+    self.request_with(&session).await
+  }
+
+  /// Makes an asynchronous request with the current parameters using the 
+  /// session's client, retrying transient failures according to its policy 
+  /// and answering from its cache when possible.
+  pub async fn request_with(self, session: &Session) -> Result<Response, Error> {
+    let url = self.image_url()?;
+    let result = fetch_image(session, &url, self.format).await;
+    session.report_result(&url, &result, |response| response.image.len() as u64);
+    result
   }
 
   /// Makes an asynchronous request with the current parameters using a reusable 
   /// http client and returns both the raw json string along with the deserialized 
   /// InfoResponse struct which has numerous helper methods.
   /// This function is recommended for anything beyond making a few 
-  /// requests as it can take advantage of keep-alive pooling.
-  pub async fn request_info(self, client: &Client) -> Result<InfoResponse, Box<dyn Error>> {
-    let parts = self.build_info_parts();
-    let url = self.build_uri(parts);
-    let response = client.get(url.clone())
-                          .send()
-                          .await?;
-    let status_code = response.status().as_u16();
-    match status_code {
-      200..=299 => {
-        let raw_json = response.text().await?;
-        let info = serde_json::from_str(&raw_json)?;
-        Ok(InfoResponse{status_code, info, raw_json, url})
-      }
-        _ => Err(Box::new(ResponseError::new(status_code)))
-    }
+  /// requests as it can take advantage of keep-alive pooling. A single attempt 
+  /// is made, see `request_info_with` to retry transient failures.
+  pub async fn request_info(self, client: &Client) -> Result<InfoResponse, Error> {
+    let mut session = Session::new(client);
+    session.retry(RetryPolicy::none());
+    self.request_info_with(&session).await
+  }
+
+  /// Requests the info.json using the session's client, retrying transient 
+  /// failures according to its policy and answering from its cache when 
+  /// possible.
+  pub async fn request_info_with(self, session: &Session) -> Result<InfoResponse, Error> {
+    fetch_info(session, &self.info_url()?).await
   }
 
   /// A convenience function that wraps around request.
-  pub async fn fetch(self) -> Result<Response, Box<dyn Error>> {
+  pub async fn fetch(self) -> Result<Response, Error> {
     let client = reqwest::Client::new();
     self.request(&client).await
   }
 
-  pub async fn fetch_info(self) ->  Result<InfoResponse, Box<dyn Error>> {
+  pub async fn fetch_info(self) ->  Result<InfoResponse, Error> {
     let client = reqwest::Client::new();
     self.request_info(&client).await
   }
-}
\ No newline at end of file
+}
+
+// Requests the image from the url, retrying through the session. The end 
+// of the request is left for the caller to report.
+pub(crate) async fn fetch_image(session: &Session, url: &Url, expected: Format) -> Result<Response, Error> {
+  session.retry.run(|| async {
+    let Fetched { status_code, headers, final_url, body } = get(session, url, Some(expected)).await?;
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    let format = verify_format(url, expected, content_type, &body)?;
+    Ok(Response{status_code, url: url.clone(), image: body, format, headers, final_url})
+  }).await
+}
+
+// Requests and deserializes an info document from the url, retrying and 
+// reporting progress through the session
+pub(crate) async fn fetch_info(session: &Session, url: &Url) -> Result<InfoResponse, Error> {
+  let result = session.retry.run(|| async {
+    let Fetched { status_code, headers, final_url, body } = get(session, url, None).await?;
+    let raw_json = String::from_utf8_lossy(&body).into_owned();
+    let info = serde_json::from_str(&raw_json)
+                          .map_err(|source| Error::Json { url: url.clone(), source })?;
+    Ok(InfoResponse{status_code, info, raw_json, url: url.clone(), headers, final_url})
+  }).await;
+  session.report_result(url, &result, |info| info.raw_json.len() as u64);
+  result
+}
+
+// A successful response body with its headers and the url after redirects
+struct Fetched {
+  status_code: u16,
+  headers: HeaderMap,
+  final_url: Url,
+  body: Bytes
+}
+
+impl Fetched {
+  fn cached(url: &Url, metadata: &Metadata, body: Bytes) -> Fetched {
+    Fetched {
+      status_code: metadata.status_code,
+      headers: metadata.header_map(),
+      final_url: metadata.final_url.as_deref().and_then(|u| Url::parse(u).ok()).unwrap_or_else(|| url.clone()),
+      body
+    }
+  }
+}
+
+// Makes a single attempt at fetching the body, a fresh cache entry is used 
+// as is and a stale one is revalidated with a conditional request. The 
+// cache is best effort, failing to read or write it never fails a request. 
+// A body is only cached once it has been verified as the expected format.
+async fn get(session: &Session, url: &Url, expected: Option<Format>) -> Result<Fetched, Error> {
+  let cached = match &session.cache {
+    Some(cache) => cache.get(url).await,
+    None => None
+  };
+  if let (Some(cache), Some(entry)) = (&session.cache, &cached) {
+    if entry.is_fresh() {
+      cache.hit();
+      session.report(|observer| observer.started(url, Some(entry.body.len() as u64)));
+      return Ok(Fetched::cached(url, &entry.metadata, entry.body.clone()))
+    }
+  }
+
+  let _permit = session.throttle(url).await;
+  let mut request = session.client.get(url.clone());
+  if let Some(entry) = &cached {
+    if let Some(etag) = &entry.metadata.etag {
+      request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &entry.metadata.last_modified {
+      request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+  }
+  let response = request.send()
+                        .await
+                        .map_err(|source| Error::Transport { url: url.clone(), source })?;
+  let status_code = response.status().as_u16();
+  match (status_code, &session.cache, cached) {
+    (304, Some(cache), Some(entry)) => {
+      cache.revalidated();
+      session.report(|observer| observer.started(url, Some(entry.body.len() as u64)));
+      match entry.metadata.revalidated(url, response.headers()) {
+        Some(metadata) => {
+          let _ = cache.update(url, &metadata).await;
+          Ok(Fetched::cached(url, &metadata, entry.body))
+        }
+        None => {
+          let _ = cache.evict(url).await;
+          Ok(Fetched::cached(url, &entry.metadata, entry.body))
+        }
+      }
+    }
+    (200..=299, cache, _) => {
+      let headers = response.headers().clone();
+      let final_url = response.url().clone();
+      let body = read_body(session, url, response).await?;
+      let content_type = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+      let verified = match expected {
+        Some(expected) => verify_format(url, expected, content_type, &body).map(|_| ()),
+        None => Ok(())
+      };
+      if let Some(cache) = cache {
+        cache.miss();
+        if verified.is_ok() {
+          let _ = cache.put(url, &final_url, status_code, &headers, &body).await;
+        }
+      }
+      verified?;
+      Ok(Fetched{ status_code, headers, final_url, body })
+    }
+    _ => Err(status_error(url, &response))
+  }
+}
+
+// The error for an unsuccessful response, keeping any Retry-After so the 
+// retry policy can honour it
+pub(crate) fn status_error(url: &Url, response: &reqwest::Response) -> Error {
+  let mut source = ResponseError::new(response.status().as_u16());
+  source.retry_after = response.headers()
+                               .get(header::RETRY_AFTER)
+                               .and_then(|value| value.to_str().ok())
+                               .and_then(parse_retry_after);
+  Error::Response { url: url.clone(), source: Box::new(source) }
+}
+
+// Reads the body a chunk at a time so the observer can follow along
+async fn read_body(session: &Session, url: &Url, mut response: reqwest::Response) -> Result<Bytes, Error> {
+  let total = response.content_length();
+  session.report(|observer| observer.started(url, total));
+  let mut body = BytesMut::with_capacity(total.unwrap_or(0).min(64 * 1024 * 1024) as usize);
+  while let Some(chunk) = response.chunk()
+                                  .await
+                                  .map_err(|source| Error::Transport { url: url.clone(), source })? {
+    body.extend_from_slice(&chunk);
+    session.report(|observer| observer.received(url, body.len() as u64, total));
+  }
+  Ok(body.freeze())
+}
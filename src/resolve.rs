@@ -0,0 +1,92 @@
+//! Rebuilding an Image from the server's authoritative base uri
+use super::*;
+use crate::errors::{Parameter, ParseError};
+use crate::requests::fetch_info;
+
+impl Image {
+  /// Creates an Image from the id of an info document, which is the image's 
+  /// base uri `{scheme}://{server}{/prefix}/{identifier}`. Everything in the 
+  /// path before the identifier is treated as prefixes, any query or 
+  /// fragment is dropped.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  ///
+  /// let api = Image::from_info_id("https://ids.lib.harvard.edu/ids/iiif/25286607")?;
+  /// assert_eq!(vec!["ids", "iiif"], api.prefixes);
+  /// ```
+  pub fn from_info_id(id: &str) -> Result<Image, ParseError> {
+    let mut url = Url::parse(id).map_err(|e| ParseError::new(Parameter::Url, id, &e.to_string()))?;
+    url.set_query(None);
+    url.set_fragment(None);
+    url.path_segments_mut()
+       .map_err(|_| ParseError::new(Parameter::Url, id, "cannot be a base url"))?
+       .pop_if_empty()
+       .push("info.json");
+    Image::from_url(url.as_str()).map_err(|e| ParseError::new(e.parameter, id, &e.details))
+  }
+
+  /// Builds the base uri for the current identifier, which servers should 
+  /// redirect to the info.json
+  pub fn base_url(&self) -> Result<Url, Error> {
+    let mut parts = self.prefixes.clone();
+    parts.push(self.identifier.clone());
+    self.build_uri(parts)
+  }
+
+  /// Fetches the info document by requesting the base uri and following the 
+  /// server's redirect, falling back to the info.json url for servers that 
+  /// don't redirect. The host, prefixes and identifier are then rebuilt from 
+  /// the document's id and the version from its context, so later requests 
+  /// use the server's authoritative base uri even when it differs from the 
+  /// one requested, such as http instead of https or a proxy path. The other 
+  /// parameters are kept.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  ///
+  /// let mut api = Image::new("http://example.org/iiif");
+  /// api.identifier("abc");
+  /// let info = api.resolve(&Session::default()).await?;
+  /// // Now https://example.org/proxy/iiif if that is the info document's id
+  /// let src = api.image_url()?;
+  /// ```
+  pub async fn resolve(&mut self, session: &Session) -> Result<InfoResponse, Error> {
+    let info = match fetch_info(session, &self.base_url()?).await {
+      Err(e) if matches!(e, Error::Json { .. }) || e.status_code().is_some_and(|s| (400..500).contains(&s)) => {
+        fetch_info(session, &self.info_url()?).await?
+      }
+      result => result?
+    };
+    let id = info.info.id();
+    if id.is_empty() {
+      return Err(ParseError::new(Parameter::Url, info.url.as_str(), "the info document has no id").into())
+    }
+    let resolved = Image::from_info_id(id)?;
+    self.host = resolved.host;
+    self.prefixes = resolved.prefixes;
+    self.identifier = resolved.identifier;
+    self.version = info.info.version();
+    Ok(info)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn from_info_id() {
+    let api = Image::from_info_id("https://example.org/proxy/iiif/ark:%2F12025%2F654/").unwrap();
+    assert_eq!("https://example.org", api.host);
+    assert_eq!(vec!["proxy", "iiif"], api.prefixes);
+    assert_eq!("ark:/12025/654", api.identifier);
+    assert_eq!("https://example.org/proxy/iiif/ark:%2F12025%2F654", api.base_url().unwrap().as_str());
+    assert!(Image::from_info_id("https://example.org").is_err());
+    assert!(Image::from_info_id("data:text/plain,abc").is_err());
+
+    let api = Image::from_info_id("https://example.org/iiif/abc?token=1#region").unwrap();
+    assert_eq!("abc", api.identifier);
+    assert_eq!("https://example.org/iiif/abc/info.json", api.info_url().unwrap().as_str());
+  }
+}
@@ -1,30 +1,39 @@
 //! Contains the response structs and their helper methods
 use super::*;
 use bytes::Bytes;
-use std::fs::File;
-use std::io::prelude::*;
 
 /// Generic response struct containing a byte array of the image
-/// the final url and status code returned 
+/// the requested and final url, status code and headers returned, along with 
+/// the format the image was detected as from its magic bytes or Content-Type. 
+/// See `ResponseHeaders` for typed access to the headers.
 #[derive(Clone, Debug)]
 pub struct Response {
   pub status_code: u16,
   pub url: Url,
-  pub image: Bytes
+  pub image: Bytes,
+  pub format: Option<Format>,
+  pub headers: HeaderMap,
+  /// The url after any redirects
+  pub final_url: Url
 }
 
 /// Contains the deserialized json info, the raw json string,
-/// the url and status code
+/// the requested and final url, status code and headers
 #[derive(Clone, Debug)]
 pub struct InfoResponse {
   pub status_code: u16,
   pub raw_json: String,
   pub info: Info,
-  pub url: Url
+  pub url: Url,
+  pub headers: HeaderMap,
+  /// The url after any redirects
+  pub final_url: Url
 }
 
 impl Response {
-  /// Writes the image response to a file at the specified relative or absolute path
+  /// Writes the image response to a file at the specified relative or absolute path 
+  /// without blocking, returning an error if the file cannot be created or written to. 
+  /// For large images see `Image::download` which never holds the whole body in memory.
   /// 
   ///  # Example
   /// ```rust,ignore
@@ -39,8 +48,7 @@ impl Response {
   ///     .expect("Writing file to disk");
   /// ```
   pub async fn write_to_file(self, path: &str) -> std::io::Result<()>{
-    let mut file = File::create(path).unwrap();
-    file.write_all(&self.image)
+    tokio::fs::write(path, &self.image).await
   }
 }
 
@@ -48,133 +56,92 @@ impl InfoResponse {
   
   /// Returns a  set of height and width pairs the client should use in the size parameter to request complete images at different sizes that the server has available. This may be used to let a client know the sizes that are available when the server does not support requests for arbitrary sizes, or simply as a hint that requesting an image of this size may result in a faster response. A request constructed with the w,h syntax using these sizes must be supported by the server, even if arbitrary width and height are not.
   pub fn sizes(&self) -> Vec<InfoSize> {
-    self.info.sizes.clone()
+    self.info.sizes().to_vec()
   }
 
   /// The width in pixels of the full image content, given as an integer.
   pub fn width(&self) -> usize {
-    self.info.width
+    self.info.width()
   }
 
   /// The height in pixels of the full image content, given as an integer.
   pub fn height(&self) -> usize {
-    self.info.height
+    self.info.height()
   }
 
   /// A set of descriptions of the parameters to use to request regions of the image (tiles) that are efficient for the server to deliver. Each description gives a width, optionally a height for non-square tiles, and a set of scale factors at which tiles of those dimensions are available.
   pub fn tiles(&self) -> Vec<Tile> {
-    self.info.tiles.clone()
+    self.info.tiles().to_vec()
   }
 
   ///  Might include copyright or ownership statements, or a simple acknowledgement of the providing institution
   pub fn attribution(&self) -> Vec<Attribution> {
-    self.info.attribution.clone()
+    match &self.info {
+      Info::V2(info) => info.attribution.clone(),
+      Info::V3(_) => Vec::new()
+    }
   }
 
-
+  /// A link to an external resource that describes the license or rights statement under which content obtained from the Image API service may be used. For 3.0 this is the single rights property.
   pub fn license(&self) -> Vec<String> {
-    self.info.license.clone()
+    match &self.info {
+      Info::V2(info) => info.license.iter().map(|license| license.id.clone()).collect(),
+      Info::V3(info) => info.rights.iter().cloned().collect()
+    }
   }
 
-  /// A link to an external resource that describes the license or rights statement under which content obtained from the Image API service may be used.
+  /// The version of the Image API the server responded with
+  pub fn version(&self) -> Version {
+    self.info.version()
+  }
+
+  /// The set of image format parameter values available for the image. If not specified then clients should assume only formats declared in the compliance level document.
   pub fn formats(&self) -> Vec<String> {
-    self.info.profile.1.formats.clone()
+    self.info.formats()
   }
 
   /// The set of image quality parameter values available for the image. If not specified then clients should assume only qualities declared in the compliance level document.
   pub fn qualities(&self) -> Vec<String> {
-    self.info.profile.1.qualities.clone()
+    self.info.qualities()
   }
 
   /// The set of features supported for the image. If not specified then clients should assume only features declared in the compliance level document.
   pub fn supports(&self) -> Vec<String> {
-    self.info.profile.1.supports.clone()
+    self.info.supports()
   }
-}
-
-// Structs for deserialization
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Info {
-  #[serde(default)]
-  #[serde(rename = "@context")]
-  pub context: String,
-  #[serde(rename = "@id")]
-  pub id: String,
-  pub protocol: String,
-  pub width: usize,
-  pub height: usize,
-  #[serde(default)]
-  pub sizes: Vec<InfoSize>,
-  #[serde(default)]
-  pub tiles: Vec<Tile>,
-  #[serde(default)]
-  pub attribution: Vec<Attribution>,
-  #[serde(default)]
-  pub logo: Logo,
-  #[serde(default)]
-  pub license: Vec<String>,
-  #[serde(default)]
-  pub profile: (String, Profile),
-  #[serde(default)]
-  pub service: Vec<Service2>,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct InfoSize {
-  pub width: usize,
-  pub height: usize,
-}
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Tile {
-  pub width: usize,
-  #[serde(rename = "scaleFactors")]
-  pub scale_factors: Vec<usize>,
-  #[serde(default)]
-  pub height: usize,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Attribution {
-  #[serde(rename = "@value")]
-  pub value: String,
-  #[serde(rename = "@language")]
-  pub language: String,
-}
+  /// The real world width and height of the full image in `mm`, `cm`, `m` or `in`, if the server provides a physical dimensions service.
+  pub fn physical_dimensions(&self, units: &str) -> Option<(f64, f64)> {
+    self.info.physical_dimensions(units)
+  }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Logo {
-  #[serde(rename = "@id")]
-  pub id: String,
-  pub service: Service,
-}
+  /// The compliance level named in the profile, if it is level 0, 1 or 2.
+  pub fn compliance_level(&self) -> Option<ComplianceLevel> {
+    self.info.compliance_level()
+  }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Service {
-  #[serde(rename = "@context")]
-  pub context: String,
-  #[serde(rename = "@id")]
-  pub id: String,
-  pub profile: String,
+  /// Whether the server supports the feature, either through its compliance level or by listing it in the profile.
+  pub fn supports_feature(&self, feature: &Feature) -> bool {
+    self.info.supports_feature(feature)
+  }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Profile {
-  pub formats: Vec<String>,
-  pub qualities: Vec<String>,
-  pub supports: Vec<String>,
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn unwritable_path() {
+    let url = Url::parse("https://example.org/iiif/abc/full/max/0/default.jpg").unwrap();
+    let response = Response {
+      status_code: 200,
+      url: url.clone(),
+      image: Bytes::from_static(b"jpg"),
+      format: None,
+      headers: HeaderMap::new(),
+      final_url: url
+    };
+    let written = response.write_to_file("/nonexistent/directory/foo.jpg").await;
+    assert_eq!(std::io::ErrorKind::NotFound, written.unwrap_err().kind());
+  }
 }
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Service2 {
-  #[serde(rename = "@context")]
-  pub context: String,
-  pub profile: String,
-  #[serde(rename = "physicalScale")]
-  pub physical_scale: f64,
-  #[serde(rename = "physicalUnits")]
-  pub physical_units: String,
-  #[serde(rename = "@id")]
-  pub id: String,
-}
\ No newline at end of file
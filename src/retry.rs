@@ -0,0 +1,151 @@
+//! Retrying transient failures with exponential backoff
+use super::*;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+/// How many times a request is attempted and how long to wait between 
+/// attempts. Transport errors and 429, 500, 502, 503 and 504 responses are 
+/// retried, see `Error::is_retryable`.
+///
+/// The wait before retry n is `initial_delay * multiplier^(n - 1)` capped at 
+/// `max_delay`, with up to half of it removed at random so that many clients 
+/// do not retry in lockstep. A Retry-After header sent by the server is 
+/// honoured in place of the backoff, unless it asks for a longer wait than 
+/// `max_delay` in which case the error is returned straight away.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+  pub max_attempts: u32,
+  pub initial_delay: Duration,
+  pub max_delay: Duration,
+  pub multiplier: f64,
+  pub jitter: bool
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy {
+      max_attempts: 4,
+      initial_delay: Duration::from_millis(500),
+      max_delay: Duration::from_secs(30),
+      multiplier: 2.0,
+      jitter: true
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// A policy that makes a single attempt and never retries
+  pub fn none() -> RetryPolicy {
+    RetryPolicy {
+      max_attempts: 1,
+      ..Default::default()
+    }
+  }
+
+  /// Sets the total number of attempts including the first, at least one 
+  /// attempt is always made
+  pub fn max_attempts(&mut self, max_attempts: u32) {
+    self.max_attempts = max_attempts.max(1);
+  }
+
+  /// Sets the wait before the first retry and the cap on any single wait
+  pub fn delays(&mut self, initial_delay: Duration, max_delay: Duration) {
+    self.initial_delay = initial_delay;
+    self.max_delay = max_delay;
+  }
+
+  /// Sets the factor the wait grows by after each retry
+  pub fn multiplier(&mut self, multiplier: f64) {
+    self.multiplier = multiplier;
+  }
+
+  /// Sets whether waits are randomly shortened by up to half
+  pub fn jitter(&mut self, jitter: bool) {
+    self.jitter = jitter;
+  }
+
+  /// The wait after the given failed attempt, starting from 1
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+    let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+    let delay = Duration::try_from_secs_f64(delay).unwrap_or(self.max_delay).min(self.max_delay);
+    match self.jitter {
+      true => delay.mul_f64(1.0 - random() / 2.0),
+      false => delay
+    }
+  }
+
+  /// Runs the request until it succeeds, fails with an error that isn't 
+  /// retryable or runs out of attempts, returning the last result
+  pub(crate) async fn run<T, F, Fut>(&self, mut request: F) -> Result<T, Error>
+  where F: FnMut() -> Fut, Fut: std::future::Future<Output = Result<T, Error>> {
+    let mut attempt = 1;
+    loop {
+      match request().await {
+        Err(e) if e.is_retryable() && attempt < self.max_attempts => {
+          let delay = match e.retry_after() {
+            Some(delay) if delay > self.max_delay => return Err(e),
+            Some(delay) => delay,
+            None => self.backoff(attempt)
+          };
+          tokio::time::sleep(delay).await;
+          attempt += 1;
+        }
+        result => return result
+      }
+    }
+  }
+}
+
+/// Parses a Retry-After header, given either as a number of seconds or an 
+/// HTTP date. Dates in the past are treated as no wait.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+  let value = value.trim();
+  match value.parse::<u64>() {
+    Ok(seconds) => Some(Duration::from_secs(seconds)),
+    Err(_) => {
+      let date = httpdate::parse_http_date(value).ok()?;
+      Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+  }
+}
+
+// A value in [0, 1) from the randomly seeded std hasher, which is plenty 
+// for spreading out retries without pulling in an rng
+fn random() -> f64 {
+  let mut hasher = RandomState::new().build_hasher();
+  hasher.write_u128(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos());
+  (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn exponential_backoff() {
+    let mut policy = RetryPolicy::default();
+    policy.jitter(false);
+    policy.delays(Duration::from_millis(100), Duration::from_secs(1));
+    assert_eq!(Duration::from_millis(100), policy.backoff(1));
+    assert_eq!(Duration::from_millis(200), policy.backoff(2));
+    assert_eq!(Duration::from_millis(800), policy.backoff(4));
+    assert_eq!(Duration::from_secs(1), policy.backoff(5));
+    assert_eq!(Duration::from_secs(1), policy.backoff(u32::MAX));
+
+    policy.jitter(true);
+    for attempt in 1..6 {
+      let delay = policy.backoff(attempt);
+      let limit = Duration::from_millis(100 * 2u64.pow(attempt - 1)).min(Duration::from_secs(1));
+      assert!(delay <= limit && delay >= limit / 2);
+    }
+  }
+
+  #[test]
+  fn retry_after_header() {
+    assert_eq!(Some(Duration::from_secs(120)), parse_retry_after("120"));
+    assert_eq!(Some(Duration::ZERO), parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"));
+    assert_eq!(None, parse_retry_after("soon"));
+  }
+}
@@ -0,0 +1,244 @@
+//! Typed descriptors for the services embedded in info.json documents
+use super::*;
+use crate::info::one_or_many;
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+use serde_json::{Map, Value};
+
+/// A service embedded in the info document. The type is detected from the 
+/// `@context`, `profile` or `type` of each entry, anything unrecognised or 
+/// malformed is kept as Other.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InfoService {
+  /// Physical dimensions service, the real world size of the image
+  PhysicalDimensions(PhysicalDimensions),
+  /// Auth 1.0 access service, covering the login, clickthrough, kiosk and external profiles
+  Auth1Login(AuthService1),
+  /// Auth 1.0 access token service
+  Auth1Token(AuthService1),
+  /// Auth 1.0 logout service
+  Auth1Logout(AuthService1),
+  /// Auth 2.0 probe service
+  Auth2Probe(AuthService2),
+  /// Auth 2.0 access service
+  Auth2Access(AuthService2),
+  /// Auth 2.0 access token service
+  Auth2Token(AuthService2),
+  /// Auth 2.0 logout service
+  Auth2Logout(AuthService2),
+  /// A link to a GeoJSON description of the image's location
+  GeoJson(GeoJson),
+  Other(Value)
+}
+
+/// The physical dimensions service, the physical scale is the size of one 
+/// pixel in physical units.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhysicalDimensions {
+  #[serde(rename = "@context", default, skip_serializing_if = "String::is_empty")]
+  pub context: String,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub profile: String,
+  #[serde(rename = "physicalScale", default, skip_serializing_if = "Option::is_none")]
+  pub physical_scale: Option<f64>,
+  #[serde(rename = "physicalUnits", default, skip_serializing_if = "Option::is_none")]
+  pub physical_units: Option<String>,
+  #[serde(rename = "@id", default, skip_serializing_if = "String::is_empty")]
+  pub id: String,
+}
+
+/// The physical dimensions service under the name earlier releases used
+#[deprecated(note = "renamed to PhysicalDimensions")]
+pub type Service2 = PhysicalDimensions;
+
+/// An Auth 1.0 service description
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthService1 {
+  #[serde(rename = "@context", default, skip_serializing_if = "String::is_empty")]
+  pub context: String,
+  #[serde(rename = "@id", default, skip_serializing_if = "String::is_empty")]
+  pub id: String,
+  pub profile: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub header: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub confirm_label: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub failure_header: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub failure_description: Option<String>,
+  #[serde(default, deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+  pub service: Vec<InfoService>,
+}
+
+/// An Auth 2.0 service description, the text properties are language maps
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthService2 {
+  pub id: String,
+  #[serde(rename = "type")]
+  pub service_type: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub profile: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub label: Option<Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub heading: Option<Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub note: Option<Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub confirm_label: Option<Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error_heading: Option<Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error_note: Option<Value>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub service: Vec<InfoService>,
+}
+
+/// A link to a GeoJSON resource
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeoJson {
+  #[serde(rename = "@context")]
+  pub context: String,
+  #[serde(rename = "@id", alias = "id")]
+  pub id: String,
+  #[serde(flatten)]
+  pub extra: Map<String, Value>,
+}
+
+impl InfoService {
+  // Pick the service type from the context, profile or type
+  fn detect(value: Value) -> InfoService {
+    let field = |key: &str| value.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+    let (context, profile) = (field("@context"), field("profile"));
+    let typed = match field("type").as_str() {
+      "AuthProbeService2" => serde_json::from_value(value.clone()).map(InfoService::Auth2Probe),
+      "AuthAccessService2" => serde_json::from_value(value.clone()).map(InfoService::Auth2Access),
+      "AuthAccessTokenService2" => serde_json::from_value(value.clone()).map(InfoService::Auth2Token),
+      "AuthLogoutService2" => serde_json::from_value(value.clone()).map(InfoService::Auth2Logout),
+      _ if context.contains("physdim") || profile.contains("physdim") => {
+        serde_json::from_value(value.clone()).map(InfoService::PhysicalDimensions)
+      }
+      _ if profile.contains("iiif.io/api/auth/1/") => match profile.rsplit('/').next() {
+        Some("token") => serde_json::from_value(value.clone()).map(InfoService::Auth1Token),
+        Some("logout") => serde_json::from_value(value.clone()).map(InfoService::Auth1Logout),
+        _ => serde_json::from_value(value.clone()).map(InfoService::Auth1Login)
+      },
+      _ if context.to_lowercase().contains("geojson") => serde_json::from_value(value.clone()).map(InfoService::GeoJson),
+      _ => return InfoService::Other(value)
+    };
+    typed.unwrap_or(InfoService::Other(value))
+  }
+}
+
+impl<'de> Deserialize<'de> for InfoService {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    Value::deserialize(deserializer).map(InfoService::detect)
+  }
+}
+
+impl Serialize for InfoService {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    match self {
+      InfoService::PhysicalDimensions(service) => service.serialize(serializer),
+      InfoService::Auth1Login(service)
+      | InfoService::Auth1Token(service)
+      | InfoService::Auth1Logout(service) => service.serialize(serializer),
+      InfoService::Auth2Probe(service)
+      | InfoService::Auth2Access(service)
+      | InfoService::Auth2Token(service)
+      | InfoService::Auth2Logout(service) => service.serialize(serializer),
+      InfoService::GeoJson(service) => service.serialize(serializer),
+      InfoService::Other(value) => value.serialize(serializer)
+    }
+  }
+}
+
+impl Info {
+  /// The real world width and height of the full image in the given units, 
+  /// one of `mm`, `cm`, `m` or `in`. Returns None without a physical 
+  /// dimensions service or when either unit is not recognised.
+  pub fn physical_dimensions(&self, units: &str) -> Option<(f64, f64)> {
+    let service = self.services().iter().find_map(|service| match service {
+      InfoService::PhysicalDimensions(dimensions) => Some(dimensions),
+      _ => None
+    })?;
+    let scale = service.physical_scale? * millimetres(service.physical_units.as_deref()?)? / millimetres(units)?;
+    Some((self.width() as f64 * scale, self.height() as f64 * scale))
+  }
+}
+
+// The length of one unit in millimetres
+fn millimetres(units: &str) -> Option<f64> {
+  match units {
+    "mm" => Some(1.0),
+    "cm" => Some(10.0),
+    "m" => Some(1000.0),
+    "in" => Some(25.4),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_service_types() {
+    let json = r#"[
+      {"@context": "http://iiif.io/api/annex/services/physdim/1/context.json",
+       "profile": "http://iiif.io/api/annex/services/physdim", "physicalScale": 0.0025, "physicalUnits": "in"},
+      {"@context": "http://iiif.io/api/auth/1/context.json", "@id": "https://example.org/login",
+       "profile": "http://iiif.io/api/auth/1/login", "label": "Login",
+       "service": [{"@id": "https://example.org/token", "profile": "http://iiif.io/api/auth/1/token"},
+                   {"@id": "https://example.org/logout", "profile": "http://iiif.io/api/auth/1/logout"}]},
+      {"id": "https://example.org/probe", "type": "AuthProbeService2",
+       "service": [{"id": "https://example.org/access", "type": "AuthAccessService2", "profile": "active",
+                    "service": [{"id": "https://example.org/token2", "type": "AuthAccessTokenService2"}]}]},
+      {"@context": "http://geojson.org/geojson-ld/geojson-context.jsonld", "@id": "http://www.geonames.org/2950159"},
+      {"@context": "http://example.org/unknown", "@id": "https://example.org/other"}
+    ]"#;
+    let services: Vec<InfoService> = serde_json::from_str(json).unwrap();
+    assert!(matches!(services[0], InfoService::PhysicalDimensions(_)));
+    match &services[1] {
+      InfoService::Auth1Login(login) => {
+        assert!(matches!(login.service[0], InfoService::Auth1Token(_)));
+        assert!(matches!(login.service[1], InfoService::Auth1Logout(_)));
+      }
+      other => panic!("Expected an auth 1.0 login service, got {:?}", other)
+    }
+    match &services[2] {
+      InfoService::Auth2Probe(probe) => match &probe.service[0] {
+        InfoService::Auth2Access(access) => assert!(matches!(access.service[0], InfoService::Auth2Token(_))),
+        other => panic!("Expected an auth 2.0 access service, got {:?}", other)
+      },
+      other => panic!("Expected an auth 2.0 probe service, got {:?}", other)
+    }
+    assert!(matches!(services[3], InfoService::GeoJson(_)));
+    assert!(matches!(services[4], InfoService::Other(_)));
+    assert_eq!(serde_json::from_str::<Value>(json).unwrap(), serde_json::to_value(&services).unwrap());
+  }
+
+  #[test]
+  fn physical_dimensions_in_units() {
+    let info = Info::V2(InfoV2{
+      width: 4000,
+      height: 2000,
+      service: vec![InfoService::PhysicalDimensions(PhysicalDimensions{
+        physical_scale: Some(0.0025),
+        physical_units: Some("in".into()),
+        ..Default::default()
+      })],
+      ..Default::default()
+    });
+    let (width, height) = info.physical_dimensions("cm").unwrap();
+    assert!((width - 25.4).abs() < 1e-9 && (height - 12.7).abs() < 1e-9);
+    assert_eq!(None, info.physical_dimensions("furlong"));
+  }
+}
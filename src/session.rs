@@ -0,0 +1,108 @@
+//! A reusable client bundled with the policies applied to every request
+use super::*;
+use std::fmt;
+use std::sync::Arc;
+
+/// Wraps a reusable http client with the retry policy, optional rate 
+/// limiter, disk cache and progress observer used by `Image::request_with`, 
+/// `Image::request_info_with` and `Image::download_with`. Cloning a session 
+/// shares the client's connection pool, the rate limiter's buckets, the 
+/// cache and the observer.
+///
+/// ```rust,ignore
+/// use iiif::*;
+///
+/// let mut retry = RetryPolicy::default();
+/// retry.max_attempts(6);
+/// let mut session = Session::new(&Client::new());
+/// session.retry(retry);
+///
+/// let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+/// api.identifier("25286607");
+/// let response = api.request_with(&session).await?;
+/// ```
+#[derive(Clone)]
+pub struct Session {
+  pub client: Client,
+  pub retry: RetryPolicy,
+  pub rate_limiter: Option<RateLimiter>,
+  pub cache: Option<DiskCache>,
+  pub progress: Option<Arc<dyn Progress>>
+}
+
+impl Session {
+  /// Creates a session on a clone of the client using the default retry policy
+  pub fn new(client: &Client) -> Session {
+    Session {
+      client: client.clone(),
+      retry: RetryPolicy::default(),
+      rate_limiter: None,
+      cache: None,
+      progress: None
+    }
+  }
+
+  /// Sets the retry policy, use `RetryPolicy::none()` to disable retries
+  pub fn retry(&mut self, policy: RetryPolicy) {
+    self.retry = policy;
+  }
+
+  /// Throttles every request sent through this session and its clones
+  pub fn rate_limit(&mut self, limiter: RateLimiter) {
+    self.rate_limiter = Some(limiter);
+  }
+
+  /// Stores responses in the cache and answers from it where the server's 
+  /// caching headers allow
+  pub fn cache(&mut self, cache: DiskCache) {
+    self.cache = Some(cache);
+  }
+
+  /// Reports the progress of every request sent through this session and 
+  /// its clones to the observer
+  pub fn progress<P: Progress + 'static>(&mut self, observer: P) {
+    self.progress = Some(Arc::new(observer));
+  }
+
+  // Passes an event to the progress observer if there is one
+  pub(crate) fn report(&self, event: impl FnOnce(&dyn Progress)) {
+    if let Some(observer) = &self.progress {
+      event(observer.as_ref());
+    }
+  }
+
+  // Reports the end of a request as finished with the body's size or failed
+  pub(crate) fn report_result<T>(&self, url: &Url, result: &Result<T, Error>, size: impl FnOnce(&T) -> u64) {
+    match result {
+      Ok(value) => self.report(|observer| observer.finished(url, size(value))),
+      Err(e) => self.report(|observer| observer.failed(url, e))
+    }
+  }
+
+  // Waits for the rate limiter if there is one, the permit is held until 
+  // the response body has been read
+  pub(crate) async fn throttle(&self, url: &Url) -> Option<RatePermit> {
+    match &self.rate_limiter {
+      Some(limiter) => Some(limiter.acquire(url).await),
+      None => None
+    }
+  }
+}
+
+impl fmt::Debug for Session {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Session")
+     .field("client", &self.client)
+     .field("retry", &self.retry)
+     .field("rate_limiter", &self.rate_limiter)
+     .field("cache", &self.cache)
+     .field("progress", &self.progress.is_some())
+     .finish()
+  }
+}
+
+impl Default for Session {
+  fn default() -> Self {
+    Session::new(&Client::new())
+  }
+}
@@ -0,0 +1,167 @@
+//! Fetches every tile of an image and composites them into a single image,
+//! enabled with the `stitch` feature
+use super::*;
+use crate::download::temp_path;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use image::{DynamicImage, RgbImage};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+impl Image {
+  /// Fetches every tile at the scale factor, with up to `concurrency`
+  /// requests in flight, and composites them into one image. This allows
+  /// fetching full resolution images from servers that refuse large sizes
+  /// but serve tiles. The whole image is held in memory, see
+  /// `stitch_to_png` for large images.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  ///
+  /// let client = Client::new();
+  /// let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+  /// api.identifier("25286607");
+  /// let info = api.clone().request_info(&client).await?;
+  /// let image = api.stitch(&client, &info.info, 1, 8).await?;
+  /// ```
+  pub async fn stitch(&self, client: &Client, info: &Info, scale_factor: usize, concurrency: usize) -> Result<DynamicImage, Error> {
+    let tiles = self.tiles(info, scale_factor).ok_or(Error::NoTiles{ scale_factor })?;
+    let (width, height) = tiles.scaled_size();
+    let mut canvas = RgbImage::new(width as u32, height as u32);
+    let fetched = fetch_tiles(client, tiles.collect(), concurrency);
+    futures::pin_mut!(fetched);
+    while let Some((tile, decoded)) = fetched.try_next().await? {
+      place(&mut canvas, &tile, &decoded, scale_factor, 0);
+    }
+    Ok(DynamicImage::ImageRgb8(canvas))
+  }
+
+  /// Fetches every tile at the scale factor and writes them to an RGB PNG
+  /// at the path one row of tiles at a time, so only a couple of rows are
+  /// ever held in memory. Each row is fetched with up to `concurrency`
+  /// requests in flight while the previous one is encoded on a blocking
+  /// thread.
+  pub async fn stitch_to_png(&self, client: &Client, info: &Info, scale_factor: usize, concurrency: usize, path: &str) -> Result<(), Error> {
+    let tiles = self.tiles(info, scale_factor).ok_or(Error::NoTiles{ scale_factor })?;
+    let (width, height) = tiles.scaled_size();
+    let columns = tiles.columns();
+    if columns == 0 {
+      return Err(Error::NoTiles{ scale_factor })
+    }
+    let tiles: Vec<Image> = tiles.collect();
+
+    // Encoded beside the path and renamed into place once complete, so a
+    // failed tile never leaves a truncated PNG at the path
+    let path = Path::new(path);
+    let tmp = temp_path(path);
+    let (strips, received) = tokio::sync::mpsc::channel(1);
+    let encoding = tmp.clone();
+    let encoder = tokio::task::spawn_blocking(move || write_png(&encoding, width, height, received));
+    let sent = send_rows(client, &tiles, columns, scale_factor, concurrency, (width, height), strips).await;
+    // The encoder only hangs up after failing, its error is preferred
+    let encoded = encoder.await.map_err(|e| Error::Io(io::Error::other(e)))?;
+    let result = match encoded.and(sent) {
+      Ok(()) => tokio::fs::rename(&tmp, path).await.map_err(Error::from),
+      Err(e) => Err(e)
+    };
+    if result.is_err() {
+      let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    result
+  }
+}
+
+// Fetches the tiles a row at a time, sending each row as a strip of the
+// full width to the encoder
+async fn send_rows(client: &Client, tiles: &[Image], columns: usize, scale_factor: usize, concurrency: usize, (width, height): (usize, usize), strips: Sender<RgbImage>) -> Result<(), Error> {
+  for row in tiles.chunks(columns) {
+    let (top, strip_height) = match &row[0].region {
+      Region::Abs(region) => (region.y / scale_factor, region.h.div_ceil(scale_factor)),
+      _ => (0, height)
+    };
+    let mut strip = RgbImage::new(width as u32, strip_height as u32);
+    let fetched = fetch_tiles(client, row.to_vec(), concurrency);
+    futures::pin_mut!(fetched);
+    while let Some((tile, decoded)) = fetched.try_next().await? {
+      place(&mut strip, &tile, &decoded, scale_factor, top);
+    }
+    if strips.send(strip).await.is_err() {
+      break
+    }
+  }
+  Ok(())
+}
+
+// Encodes the strips as they arrive into an RGB PNG at the path
+fn write_png(path: &Path, width: usize, height: usize, mut strips: Receiver<RgbImage>) -> Result<(), Error> {
+  let file = BufWriter::new(File::create(path)?);
+  let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+  encoder.set_color(png::ColorType::Rgb);
+  encoder.set_depth(png::BitDepth::Eight);
+  let mut writer = encoder.write_header().map_err(png_error)?;
+  let mut rows = writer.stream_writer().map_err(png_error)?;
+  while let Some(strip) = strips.blocking_recv() {
+    rows.write_all(strip.as_raw())?;
+  }
+  rows.finish().map_err(png_error)?;
+  writer.finish().map_err(png_error)
+}
+
+// Requests and decodes the tiles, yielding them as they complete
+fn fetch_tiles(client: &Client, tiles: Vec<Image>, concurrency: usize) -> impl Stream<Item = Result<(Image, DynamicImage), Error>> + '_ {
+  stream::iter(tiles)
+    .map(move |tile| async move {
+      let response = tile.clone().request(client).await?;
+      let decoded = image::load_from_memory(&response.image)
+                          .map_err(|source| Error::Decode{ url: response.url, source: Box::new(source) })?;
+      Ok((tile, decoded))
+    })
+    .buffer_unordered(concurrency.max(1))
+}
+
+// Copy a decoded tile onto the canvas at its scaled position, the canvas
+// starts `top` pixels down the scaled image
+fn place(canvas: &mut RgbImage, tile: &Image, decoded: &DynamicImage, scale_factor: usize, top: usize) {
+  if let Region::Abs(region) = &tile.region {
+    let x = region.x / scale_factor;
+    let y = region.y / scale_factor - top;
+    image::imageops::replace(canvas, &decoded.to_rgb8(), x as i64, y as i64);
+  }
+}
+
+fn png_error(e: png::EncodingError) -> Error {
+  match e {
+    png::EncodingError::IoError(e) => Error::Io(e),
+    e => Error::Io(io::Error::other(e))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::Rgb;
+
+  #[test]
+  fn places_scaled_tiles() {
+    let info = Info::V2(InfoV2{
+      width: 1000,
+      height: 700,
+      tiles: vec![Tile{ width: 256, height: 0, scale_factors: vec![2], ..Default::default() }],
+      ..Default::default()
+    });
+    let api = Image::new("https://example.org/iiif");
+    let tiles: Vec<Image> = api.tiles(&info, 2).unwrap().collect();
+    let mut canvas = RgbImage::new(500, 350);
+    let red = DynamicImage::ImageRgb8(RgbImage::from_pixel(244, 94, Rgb([255, 0, 0])));
+    place(&mut canvas, &tiles[3], &red, 2, 0);
+    assert_eq!(&Rgb([0, 0, 0]), canvas.get_pixel(255, 255));
+    assert_eq!(&Rgb([255, 0, 0]), canvas.get_pixel(256, 256));
+    assert_eq!(&Rgb([255, 0, 0]), canvas.get_pixel(499, 349));
+
+    // The second row strip starts 256 pixels down
+    let mut strip = RgbImage::new(500, 94);
+    place(&mut strip, &tiles[3], &red, 2, 256);
+    assert_eq!(&Rgb([255, 0, 0]), strip.get_pixel(256, 0));
+  }
+}
@@ -0,0 +1,174 @@
+//! Turns the tile descriptions from info.json into image requests
+use super::*;
+
+/// An iterator over every tile request at a single scale factor, row by row 
+/// from the top left of the image. Created with `Image::tiles`.
+#[derive(Debug, Clone)]
+pub struct Tiles {
+  base: Image,
+  width: usize,
+  height: usize,
+  span_width: usize,
+  span_height: usize,
+  scale_factor: usize,
+  columns: usize,
+  rows: usize,
+  count: usize,
+  next: usize
+}
+
+impl Image {
+  /// Creates an iterator over the tiles of the image at the given scale 
+  /// factor, using the first tile description in the info document that 
+  /// lists it. Each tile keeps the host, identifier, rotation, quality, 
+  /// format and version of this image and sets the region and size per the 
+  /// tile algorithm, `w,` for 2.x and `w,h` for 3.0. Returns None if no tile 
+  /// description lists the scale factor, or if the tile size and scale 
+  /// factor are too large to address.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  /// 
+  /// let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+  /// api.identifier("25286607");
+  /// let info = api.clone().fetch_info().await?;
+  /// for tile in api.tiles(&info.info, 1).expect("Full resolution tiles") {
+  ///   let response = tile.request(&client).await?;
+  /// }
+  /// ```
+  pub fn tiles(&self, info: &Info, scale_factor: usize) -> Option<Tiles> {
+    let tile = info.tiles()
+                   .iter()
+                   .find(|tile| tile.scale_factors.contains(&scale_factor))?;
+    if tile.width == 0 || scale_factor == 0 {
+      return None;
+    }
+    let tile_height = match tile.height {
+      0 => tile.width,
+      height => height
+    };
+    let (width, height) = (info.width(), info.height());
+    // The region each tile covers on the full image
+    let span_width = tile.width.checked_mul(scale_factor)?;
+    let span_height = tile_height.checked_mul(scale_factor)?;
+    let columns = width.div_ceil(span_width);
+    let rows = height.div_ceil(span_height);
+    Some(Tiles{
+      base: self.clone(),
+      width,
+      height,
+      span_width,
+      span_height,
+      scale_factor,
+      columns,
+      rows,
+      count: columns.checked_mul(rows)?,
+      next: 0
+    })
+  }
+}
+
+impl Tiles {
+  /// The number of tiles across the image
+  pub fn columns(&self) -> usize {
+    self.columns
+  }
+
+  /// The number of tiles down the image
+  pub fn rows(&self) -> usize {
+    self.rows
+  }
+
+  /// The scale factor the tiles are requested at
+  pub fn scale_factor(&self) -> usize {
+    self.scale_factor
+  }
+
+  /// The width and height of the full image at this scale factor, the size 
+  /// of all the tiles put together
+  pub fn scaled_size(&self) -> (usize, usize) {
+    (self.width.div_ceil(self.scale_factor), self.height.div_ceil(self.scale_factor))
+  }
+
+  // The request for the tile at a column and row
+  fn tile(&self, column: usize, row: usize) -> Image {
+    let s = self.scale_factor;
+    let x = column * self.span_width;
+    let y = row * self.span_height;
+    let w = self.span_width.min(self.width - x);
+    let h = self.span_height.min(self.height - y);
+    let mut image = self.base.clone();
+    image.region = Region::Abs(Absolute{ x, y, w, h });
+    image.size = match self.base.version {
+      Version::V2 => Size::W(w.div_ceil(s)),
+      Version::V3 => Size::WH(w.div_ceil(s), h.div_ceil(s))
+    };
+    image
+  }
+}
+
+impl Iterator for Tiles {
+  type Item = Image;
+
+  fn next(&mut self) -> Option<Image> {
+    if self.next >= self.count {
+      return None;
+    }
+    let tile = self.tile(self.next % self.columns, self.next / self.columns);
+    self.next += 1;
+    Some(tile)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let remaining = self.count - self.next;
+    (remaining, Some(remaining))
+  }
+}
+
+impl ExactSizeIterator for Tiles {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn info(tile: Tile) -> Info {
+    Info::V2(InfoV2{ width: 1000, height: 700, tiles: vec![tile], ..Default::default() })
+  }
+
+  #[test]
+  fn edge_tiles_are_clipped() {
+    let info = info(Tile{ width: 256, height: 0, scale_factors: vec![1, 2, 4], ..Default::default() });
+    let api = Image::new("https://example.org/iiif");
+    let tiles: Vec<Image> = api.tiles(&info, 2).unwrap().collect();
+    assert_eq!(4, tiles.len());
+    assert_eq!(Region::Abs(Absolute{ x: 512, y: 512, w: 488, h: 188 }), tiles[3].region);
+    assert_eq!(Size::W(244), tiles[3].size);
+    assert!(api.tiles(&info, 8).is_none());
+  }
+
+  #[test]
+  fn non_square_v3_tiles() {
+    let info = info(Tile{ width: 512, height: 256, scale_factors: vec![1], ..Default::default() });
+    let mut api = Image::new("https://example.org/iiif");
+    api.version(Version::V3);
+    let tiles = api.tiles(&info, 1).unwrap();
+    assert_eq!((2, 3), (tiles.columns(), tiles.rows()));
+    let last = tiles.last().unwrap();
+    assert_eq!(Region::Abs(Absolute{ x: 512, y: 512, w: 488, h: 188 }), last.region);
+    assert_eq!(Size::WH(488, 188), last.size);
+  }
+
+  #[test]
+  fn overflowing_tiles_are_refused() {
+    let api = Image::new("https://example.org/iiif");
+    let info = info(Tile{ width: 1 << 40, height: 0, scale_factors: vec![1 << 40], ..Default::default() });
+    assert!(api.tiles(&info, 1 << 40).is_none());
+    let huge = Info::V2(InfoV2{
+      width: usize::MAX,
+      height: usize::MAX,
+      tiles: vec![Tile{ width: 1, height: 0, scale_factors: vec![1], ..Default::default() }],
+      ..Default::default()
+    });
+    assert!(api.tiles(&huge, 1).is_none());
+  }
+}
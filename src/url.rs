@@ -1,4 +1,8 @@
 use super::*;
+use crate::errors::{Error, Parameter, ParseError};
+use percent_encoding::percent_decode_str;
+use std::fmt;
+use std::str::FromStr;
 
 impl Image {
   pub(crate) fn build_parts(&self) -> Vec<String> {
@@ -6,14 +10,22 @@ impl Image {
     let mut info = vec![
       self.identifier.clone(),
       self.region.to_string(),
-      self.size.to_string(),
+      self.size_param(),
       self.rotation.to_string(),
-      format!("{}.{}", self.quality.to_string(), self.format.to_string()),
+      format!("{}.{}", self.quality, self.format),
     ];
     out.append(&mut info);
     out
   }
 
+  // Full was removed from 3.0 in favour of max
+  pub(crate) fn size_param(&self) -> String {
+    match (self.version, &self.size) {
+      (Version::V3, Size::Full) => Size::Max.to_string(),
+      (_, size) => size.to_string()
+    }
+  }
+
   pub(crate) fn build_info_parts(&self) -> Vec<String> {
     let mut out = self.prefixes.clone();
     let mut info = vec![self.identifier.to_string(), "info.json".to_string()];
@@ -21,15 +33,219 @@ impl Image {
     out.to_vec()
   }
 
-  pub(crate) fn build_uri(self, parts: Vec<String>) -> Url {
-    let mut url = Url::parse(&self.host).expect("Parsing URL Host");
+  pub(crate) fn build_uri(&self, parts: Vec<String>) -> Result<Url, Error> {
+    let url_error = |details: String| Error::Url { host: self.host.clone(), details };
+    let mut url = Url::parse(&self.host).map_err(|e| url_error(e.to_string()))?;
 
     for part in parts {
       url.path_segments_mut()
-          .expect("Constructing URL Parts")
+          .map_err(|_| url_error("host cannot be a base url".into()))?
           .pop_if_empty()
           .push(&part);       
     }
-    url
+    Ok(url)
+  }
+
+  /// Builds the image request url from the current parameters without 
+  /// making a request, failing if the host is not a valid base url or an 
+  /// upscaling size is used with version 2, which has no syntax for it.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  /// 
+  /// let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+  /// api.identifier("25286607");
+  /// api.width(500);
+  /// let src = api.image_url()?;
+  /// ```
+  pub fn image_url(&self) -> Result<Url, Error> {
+    if self.version == Version::V2 && self.size.is_upscaled() {
+      let size = self.size.to_string();
+      return Err(ParseError::new(Parameter::Size, &size, "upscaling sizes require version 3.0").into())
+    }
+    self.build_uri(self.build_parts())
+  }
+
+  /// Builds the image request url, kept for existing callers
+  #[deprecated(note = "renamed to image_url")]
+  pub fn url(&self) -> Result<Url, Error> {
+    self.image_url()
+  }
+
+  /// Builds the info.json url for the current identifier without making a 
+  /// request, failing if the host is not a valid base url.
+  pub fn info_url(&self) -> Result<Url, Error> {
+    self.build_uri(self.build_info_parts())
+  }
+
+  /// Parses a full Image API url, either an image request or an info.json 
+  /// request, back into an Image struct. Everything in the path before the 
+  /// identifier is treated as prefixes.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  /// 
+  /// let url = "https://ids.lib.harvard.edu/ids/iiif/25286607/full/500,/0/default.jpg";
+  /// let api = Image::from_url(url)?;
+  /// assert_eq!(api.size, Size::W(500));
+  /// ```
+  pub fn from_url(url: &str) -> Result<Image, ParseError> {
+    let not_base = || ParseError::new(Parameter::Url, url, "expected an absolute url with a host");
+    let parsed = Url::parse(url).map_err(|e| ParseError::new(Parameter::Url, url, &e.to_string()))?;
+    if !parsed.has_host() {
+      return Err(not_base());
+    }
+    let mut segments: Vec<String> = parsed.path_segments()
+                                          .ok_or_else(not_base)?
+                                          .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned())
+                                          .collect();
+    if segments.last().is_some_and(|s| s.is_empty()) {
+      segments.pop();
+    }
+
+    let mut base = parsed.clone();
+    base.set_path("");
+    base.set_query(None);
+    base.set_fragment(None);
+    let mut api = Image::new(base.as_str().trim_end_matches('/'));
+
+    if segments.last().map(String::as_str) == Some("info.json") {
+      segments.pop();
+    } else {
+      if segments.len() < 5 {
+        let details = "expected {identifier}/{region}/{size}/{rotation}/{quality}.{format} or {identifier}/info.json";
+        return Err(ParseError::new(Parameter::Url, url, details));
+      }
+      let last = segments.pop().unwrap_or_default();
+      let (quality, format) = last.rsplit_once('.')
+                                  .ok_or_else(|| ParseError::new(Parameter::Format, &last, "expected {quality}.{format}"))?;
+      api.format = format.parse()?;
+      api.quality = quality.parse()?;
+      api.rotation = segments.pop().unwrap_or_default().parse()?;
+      api.size = segments.pop().unwrap_or_default().parse()?;
+      if api.size.is_upscaled() {
+        api.version = Version::V3;
+      }
+      api.region = segments.pop().unwrap_or_default().parse()?;
+    }
+
+    api.identifier = match segments.pop() {
+      Some(identifier) if !identifier.is_empty() => identifier,
+      _ => return Err(ParseError::new(Parameter::Identifier, url, "missing identifier"))
+    };
+    api.prefixes = segments;
+    Ok(api)
+  }
+}
+
+/// Writes the image request url exactly as the parameters are set, which is 
+/// not necessarily the canonical form the server would redirect to. Call 
+/// `canonicalize` with the image's Info first to display that. If the host 
+/// is not a valid base url the raw host and parameters are joined with 
+/// slashes instead.
+impl fmt::Display for Image {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.image_url() {
+      Ok(url) => write!(f, "{}", url),
+      Err(_) => write!(f, "{}/{}", self.host.trim_end_matches('/'), self.build_parts().join("/"))
+    }
+  }
+}
+
+impl FromStr for Image {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Image::from_url(s)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  const IMAGE: &str = "https://ids.lib.harvard.edu/ids/iiif/25286607/full/500,/0/default.jpg";
+  const INFO: &str = "https://ids.lib.harvard.edu/ids/iiif/25286607/info.json";
+
+  #[test]
+  fn image_url_round_trip() {
+    let api = Image::from_url(IMAGE).unwrap();
+    assert_eq!("https://ids.lib.harvard.edu", api.host);
+    assert_eq!(vec!["ids", "iiif"], api.prefixes);
+    assert_eq!("25286607", api.identifier);
+    assert_eq!(Size::W(500), api.size);
+    let parts = api.build_parts();
+    assert_eq!(IMAGE, api.build_uri(parts).unwrap().as_str());
+  }
+
+  #[test]
+  fn info_url_round_trip() {
+    let api = Image::from_url(INFO).unwrap();
+    assert_eq!("25286607", api.identifier);
+    let parts = api.build_info_parts();
+    assert_eq!(INFO, api.build_uri(parts).unwrap().as_str());
+  }
+
+  #[test]
+  fn v3_full_size_is_max() {
+    let mut api = Image::new("https://example.org/iiif");
+    api.identifier("abc");
+    api.version(Version::V3);
+    let parts = api.build_parts();
+    assert_eq!("https://example.org/iiif/abc/full/max/0/default.jpg", api.build_uri(parts).unwrap().as_str());
+  }
+
+  #[test]
+  fn display_matches_url() {
+    let api = Image::from_url(IMAGE).unwrap();
+    assert_eq!(IMAGE, api.to_string());
+    assert_eq!(api.image_url().unwrap().as_str(), api.to_string());
+    #[allow(deprecated)]
+    let url = api.url().unwrap();
+    assert_eq!(IMAGE, url.as_str());
+  }
+
+  #[test]
+  fn upscaling_requires_v3() {
+    let mut api = Image::new("https://example.org/iiif");
+    api.identifier("abc");
+    api.upscaled_width(2000);
+    assert_eq!(Version::V3, api.version);
+    assert_eq!("https://example.org/iiif/abc/full/^2000,/0/default.jpg", api.image_url().unwrap().as_str());
+    api.version(Version::V2);
+    assert!(matches!(api.image_url(), Err(Error::Parse(ParseError { parameter: Parameter::Size, .. }))));
+  }
+
+  #[test]
+  fn host_without_scheme() {
+    let api = Image::new("ids.lib.harvard.edu/ids/iiif");
+    assert!(matches!(api.image_url(), Err(Error::Url { .. })));
+    assert!(matches!(api.info_url(), Err(Error::Url { .. })));
+  }
+
+  #[test]
+  fn host_cannot_be_a_base() {
+    let api = Image::new("mailto:images@example.org");
+    match api.image_url() {
+      Err(Error::Url { details, .. }) => assert_eq!("host cannot be a base url", details),
+      other => panic!("Expected a url error, got {:?}", other)
+    }
+  }
+
+  #[test]
+  fn encoded_identifier() {
+    let api: Image = "https://example.org/iiif/ark:%2F12025%2F654/full/max/!90/gray.png".parse().unwrap();
+    assert_eq!("ark:/12025/654", api.identifier);
+    assert_eq!(Rotation::Mirror(90.0), api.rotation);
+    assert_eq!(Format::Png, api.format);
+  }
+
+  #[test]
+  fn reports_failed_parameter() {
+    let err = Image::from_url("https://example.org/iiif/abc/full/full/0/default.bmp").unwrap_err();
+    assert_eq!(Parameter::Format, err.parameter);
+    let err = Image::from_url("https://example.org/iiif/abc/full/huge/0/default.jpg").unwrap_err();
+    assert_eq!(Parameter::Size, err.parameter);
+    let err = Image::from_url("not a url").unwrap_err();
+    assert_eq!(Parameter::Url, err.parameter);
   }
-}
\ No newline at end of file
+}
@@ -1,16 +1,51 @@
 //! Helper functions
 
 // Limit float resolution down to 3 decimal places
-pub(crate) fn format_floats<T: std::fmt::Display>(floats: Vec<T>) -> Vec<String> {
-  floats.iter()
-        .map(|float| format!("{:.5}", float.to_string()))
+pub(crate) fn format_floats(floats: Vec<f32>) -> Vec<String> {
+  floats.into_iter()
+        .map(format_float)
         .collect()
 }
 
+// Round a float to 3 decimal places, dropping any trailing zeros
+pub(crate) fn format_float(float: f32) -> String {
+  let rounded = format!("{:.3}", float);
+  let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+  match trimmed {
+    "-0" => "0".to_string(),
+    _ => trimmed.to_string()
+  }
+}
+
 // Convert coords to iiif parameter string
 pub(crate) fn join_coords<T: ToString>(x: T, y: T, w: T, h: T) -> String {
   [x, y, w, h].iter()
               .map(|s| s.to_string())
               .collect::<Vec<String>>()
               .join(",")
-}
\ No newline at end of file
+}
+
+// Split an x,y,w,h parameter string into its four coords, describing the 
+// offending token on failure
+pub(crate) fn split_coords<T: std::str::FromStr>(coords: &str) -> Result<[T; 4], String> {
+  use std::convert::TryInto;
+  let tokens: Vec<&str> = coords.split(',').collect();
+  if tokens.len() != 4 {
+    return Err(format!("expected 4 comma separated values, found {}", tokens.len()));
+  }
+  let mut parsed = Vec::with_capacity(4);
+  for token in tokens {
+    parsed.push(token.parse().map_err(|_| format!("{:?} is not a valid number", token))?);
+  }
+  parsed.try_into().map_err(|_| "expected 4 comma separated values".to_string())
+}
+
+// Parse one side of a w,h pair where an empty string means unspecified
+pub(crate) fn parse_dimension(token: &str) -> Result<Option<usize>, String> {
+  match token {
+    "" => Ok(None),
+    _ => token.parse()
+              .map(Some)
+              .map_err(|_| format!("{:?} is not a whole number of pixels", token))
+  }
+}
@@ -0,0 +1,183 @@
+//! Checks an image request against what the server says it supports
+use super::*;
+use std::fmt;
+
+/// A reason the server would refuse the current request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+  /// The format is neither part of the compliance level nor listed in the profile
+  UnsupportedFormat(Format),
+  /// The quality is neither part of the compliance level nor listed in the profile
+  UnsupportedQuality(Quality),
+  /// The request relies on a feature missing from the server's supported features
+  UnsupportedFeature(Feature),
+  /// The region does not overlap the image or has no width or height
+  RegionOutOfBounds(Absolute),
+  /// The size scales the region down to nothing
+  EmptySize,
+  /// The size is larger than the extracted region without using an upscaling form
+  UpscalingRequired { width: usize, height: usize },
+  /// The size exceeds maxWidth, maxHeight or maxArea
+  AboveMaximum { width: usize, height: usize }
+}
+
+impl fmt::Display for Violation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Violation::UnsupportedFormat(format) => write!(f, "The server does not support the {} format", format),
+      Violation::UnsupportedQuality(quality) => write!(f, "The server does not support the {} quality", quality),
+      Violation::UnsupportedFeature(feature) => write!(f, "The server does not support {}", feature),
+      Violation::RegionOutOfBounds(region) => write!(f, "The region {} is outside of the image", region),
+      Violation::EmptySize => write!(f, "The size scales the region to zero pixels"),
+      Violation::UpscalingRequired { width, height } => write!(f, "{}x{} is larger than the region, use an upscaling size", width, height),
+      Violation::AboveMaximum { width, height } => write!(f, "{}x{} exceeds the maximum size of the image", width, height)
+    }
+  }
+}
+
+impl std::error::Error for Violation {}
+
+impl Image {
+  /// Checks the request against the server's compliance level and profile, 
+  /// reporting every parameter the server is not required to support. The 
+  /// features, formats and qualities implied by the compliance level are 
+  /// combined with those listed in the profile.
+  ///
+  /// ```rust,ignore
+  /// use iiif::*;
+  /// 
+  /// let mut api = Image::new("https://ids.lib.harvard.edu/ids/iiif");
+  /// api.identifier("25286607");
+  /// let info = api.clone().fetch_info().await?;
+  /// api.webp();
+  /// api.mirrored();
+  /// for violation in api.validate(&info.info).unwrap_err() {
+  ///   println!("{}", violation);
+  /// }
+  /// ```
+  pub fn validate(&self, info: &Info) -> Result<(), Vec<Violation>> {
+    let mut violations = Vec::new();
+    if !info.supports_format(&self.format) {
+      violations.push(Violation::UnsupportedFormat(self.format));
+    }
+    if !info.supports_quality(&self.quality) {
+      violations.push(Violation::UnsupportedQuality(self.quality.clone()));
+    }
+    for feature in self.required_features(info) {
+      if !info.supports_feature(&feature) {
+        violations.push(Violation::UnsupportedFeature(feature));
+      }
+    }
+    if let Err(violation) = self.dimensions(info) {
+      violations.push(violation);
+    }
+
+    match violations.is_empty() {
+      true => Ok(()),
+      false => Err(violations)
+    }
+  }
+
+  // The features the current parameters rely on
+  fn required_features(&self, info: &Info) -> Vec<Feature> {
+    let mut features = Vec::new();
+    match self.region {
+      Region::Full => {},
+      Region::Square => features.push(Feature::RegionSquare),
+      Region::Abs(_) => features.push(Feature::RegionByPx),
+      Region::Pct(_) => features.push(Feature::RegionByPct)
+    }
+    let listed = |w: usize, h: usize| info.sizes().iter().any(|s| s.width == w && s.height == h);
+    match self.size {
+      Size::Full | Size::Max => {},
+      Size::W(_) => features.push(Feature::SizeByW),
+      Size::H(_) => features.push(Feature::SizeByH),
+      Size::Pct(_) => features.push(Feature::SizeByPct),
+      // Sizes listed in the info document must be supported regardless
+      Size::WH(w, h) if listed(w, h) => {},
+      Size::WH(..) => features.push(Feature::SizeByWh),
+      Size::LtWH(..) => features.push(Feature::SizeByConfinedWh),
+      _ => features.push(Feature::SizeUpscaling)
+    }
+    let degrees = match self.rotation {
+      Rotation::Normal(n) => n,
+      Rotation::Mirror(n) => {
+        features.push(Feature::Mirroring);
+        n
+      }
+    };
+    let degrees = degrees % 360.0;
+    if degrees != 0.0 {
+      match degrees % 90.0 == 0.0 {
+        true => features.push(Feature::RotationBy90s),
+        false => features.push(Feature::RotationArbitrary)
+      }
+    }
+    features
+  }
+
+  /// Refuses upscaling sizes unless the info document lists sizeUpscaling 
+  /// in its supported features.
+  pub fn validate_upscaling(&self, info: &Info) -> Result<(), Violation> {
+    match self.size.is_upscaled() && !info.supports_feature(&Feature::SizeUpscaling) {
+      true => Err(Violation::UnsupportedFeature(Feature::SizeUpscaling)),
+      false => Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn refuses_upscaling() {
+    let mut info = InfoV3::default();
+    let mut api = Image::new("https://example.org/iiif");
+    api.upscaled_width(8000);
+    let refused = api.validate_upscaling(&Info::V3(info.clone()));
+    assert_eq!(Err(Violation::UnsupportedFeature(Feature::SizeUpscaling)), refused);
+    info.extra_features.push("sizeUpscaling".into());
+    assert_eq!(Ok(()), api.validate_upscaling(&Info::V3(info)));
+  }
+
+  #[test]
+  fn reports_every_violation() {
+    let info = Info::V2(InfoV2{
+      width: 6000,
+      height: 4000,
+      profile: vec![
+        ProfileEntry::Uri("http://iiif.io/api/image/2/level1.json".into()),
+        ProfileEntry::Object(Profile{
+          formats: vec!["png".into()],
+          max_width: Some(2000),
+          ..Default::default()
+        })
+      ],
+      ..Default::default()
+    });
+    let mut api = Image::new("https://example.org/iiif");
+    api.pct_region(0.0, 0.0, 50.0, 50.0);
+    api.width(2500);
+    api.mirrored();
+    api.webp();
+    assert_eq!(Err(vec![
+      Violation::UnsupportedFormat(Format::Webp),
+      Violation::UnsupportedFeature(Feature::RegionByPct),
+      Violation::UnsupportedFeature(Feature::Mirroring),
+      Violation::AboveMaximum{ width: 2500, height: 1667 }
+    ]), api.validate(&info));
+
+    api.full_region();
+    api.width(1000);
+    api.rotation = Rotation::Normal(0.0);
+    api.png();
+    assert_eq!(Ok(()), api.validate(&info));
+    api.rotate_right();
+    api.gray();
+    assert_eq!(Err(vec![
+      Violation::UnsupportedQuality(Quality::Gray),
+      Violation::UnsupportedFeature(Feature::RotationBy90s)
+    ]), api.validate(&info));
+  }
+}
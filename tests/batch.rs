@@ -0,0 +1,28 @@
+use futures::stream::{self, StreamExt};
+use iiif::*;
+use std::time::{Duration, Instant};
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+#[tokio::test]
+async fn bounds_requests_in_flight() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200)
+                    .set_body_raw(vec![0xFF, 0xD8, 0xFF, 0xE0], "image/jpeg")
+                    .set_delay(Duration::from_millis(200)))
+    .expect(6)
+    .mount(&server)
+    .await;
+
+  let mut batch = BatchDownloader::new(&Client::new());
+  batch.concurrency(2);
+  let images = stream::iter(0..6).map(|n| common::image(&server, &n.to_string()));
+  // Six requests two at a time take at least three rounds
+  let start = Instant::now();
+  let results: Vec<_> = batch.download(images).collect().await;
+  assert!(start.elapsed() >= Duration::from_millis(600));
+  assert!(results.iter().all(|(_, result)| result.is_ok()));
+}
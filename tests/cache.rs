@@ -0,0 +1,137 @@
+use iiif::*;
+use std::path::PathBuf;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+const INFO: &str = include_str!("fixtures/info/cantaloupe_2.json");
+
+fn session(name: &str) -> (Session, DiskCache, PathBuf) {
+  let dir = common::temp_path(name);
+  let cache = DiskCache::open(&dir, 1024 * 1024).unwrap();
+  let mut session = common::session();
+  session.cache(cache.clone());
+  (session, cache, dir)
+}
+
+#[tokio::test]
+async fn fresh_entries_skip_the_server() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc/info.json"))
+    .respond_with(ResponseTemplate::new(200)
+                    .insert_header("Cache-Control", "max-age=3600")
+                    .insert_header("Link", "<http://iiif.io/api/image/2/level2.json>;rel=\"profile\"")
+                    .set_body_string(INFO))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let (session, cache, dir) = session("fresh");
+  let first = common::image(&server, "abc").request_info_with(&session).await.unwrap();
+  let second = common::image(&server, "abc").request_info_with(&session).await.unwrap();
+  assert_eq!(first.raw_json, second.raw_json);
+  assert_eq!(first.profile_link(), second.profile_link());
+  assert_eq!(first.final_url, second.final_url);
+  assert_eq!(CacheStats{ hits: 1, misses: 1, revalidated: 0 }, cache.stats());
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn stale_entries_are_revalidated() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc/full/full/0/default.jpg"))
+    .and(header("If-None-Match", "\"v1\""))
+    .respond_with(ResponseTemplate::new(304))
+    .expect(2)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/abc/full/full/0/default.jpg"))
+    .respond_with(ResponseTemplate::new(200)
+                    .insert_header("Cache-Control", "no-cache")
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_bytes(b"jpg".to_vec()))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let (session, cache, dir) = session("stale");
+  for _ in 0..3 {
+    let response = common::image(&server, "abc").request_with(&session).await.unwrap();
+    assert_eq!(200, response.status_code);
+    assert_eq!(&b"jpg"[..], &response.image[..]);
+  }
+  assert_eq!(CacheStats{ hits: 0, misses: 1, revalidated: 2 }, cache.stats());
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn no_store_is_not_cached() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200)
+                    .insert_header("Cache-Control", "no-store")
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_string(INFO))
+    .expect(2)
+    .mount(&server)
+    .await;
+
+  let (session, cache, dir) = session("no-store");
+  common::image(&server, "abc").request_info_with(&session).await.unwrap();
+  common::image(&server, "abc").request_info_with(&session).await.unwrap();
+  assert_eq!(0, cache.size());
+  assert_eq!(2, cache.stats().misses);
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn mismatched_formats_are_not_cached() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc/full/full/0/default.jpg"))
+    .respond_with(ResponseTemplate::new(200)
+                    .insert_header("Cache-Control", "max-age=3600")
+                    .set_body_raw("<html>Not found</html>", "text/html"))
+    .expect(2)
+    .mount(&server)
+    .await;
+
+  let (session, cache, dir) = session("mismatch");
+  for _ in 0..2 {
+    let err = common::image(&server, "abc").request_with(&session).await.unwrap_err();
+    assert!(matches!(err, Error::FormatMismatch { .. }));
+  }
+  assert_eq!(0, cache.size());
+  assert_eq!(CacheStats{ hits: 0, misses: 2, revalidated: 0 }, cache.stats());
+  std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn no_store_revalidation_evicts() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(header("If-None-Match", "\"v1\""))
+    .respond_with(ResponseTemplate::new(304).insert_header("Cache-Control", "no-store"))
+    .expect(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200)
+                    .insert_header("Cache-Control", "no-cache")
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_string(INFO))
+    .expect(2)
+    .mount(&server)
+    .await;
+
+  let (session, cache, dir) = session("no-store-304");
+  for _ in 0..3 {
+    common::image(&server, "abc").request_info_with(&session).await.unwrap();
+  }
+  assert_eq!(CacheStats{ hits: 0, misses: 2, revalidated: 1 }, cache.stats());
+  std::fs::remove_dir_all(dir).unwrap();
+}
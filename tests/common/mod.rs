@@ -0,0 +1,25 @@
+//! Setup shared by the integration tests, each of which uses only some of it
+#![allow(dead_code)]
+use iiif::*;
+use std::path::PathBuf;
+use wiremock::MockServer;
+
+/// An image with the identifier served by the mock server
+pub fn image(server: &MockServer, identifier: &str) -> Image {
+  let mut api = Image::new(&server.uri());
+  api.identifier(identifier);
+  api
+}
+
+/// A session on a new client that doesn't retry, tests that exercise 
+/// retries set their own policy
+pub fn session() -> Session {
+  let mut session = Session::new(&Client::new());
+  session.retry(RetryPolicy::none());
+  session
+}
+
+/// A path in the temp directory unique to this test process
+pub fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("iiif-{}-{}", std::process::id(), name))
+}
@@ -0,0 +1,180 @@
+use iiif::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+fn image(server: &MockServer) -> Image {
+  let mut api = common::image(server, "abc");
+  api.tif();
+  api
+}
+
+#[derive(Clone, Default)]
+struct Received(Arc<AtomicU64>);
+
+impl Progress for Received {
+  fn received(&self, _url: &Url, received: u64, total: Option<u64>) {
+    assert_eq!(Some(64 * 1024), total);
+    self.0.store(received, Ordering::SeqCst);
+  }
+}
+
+#[tokio::test]
+async fn streams_to_disk_with_progress() {
+  let server = MockServer::start().await;
+  let body = vec![7u8; 64 * 1024];
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200).set_body_bytes(body.clone()))
+    .mount(&server)
+    .await;
+
+  let path = common::temp_path("download.tif");
+  let seen = Received::default();
+  let mut session = Session::default();
+  session.progress(seen.clone());
+
+  let download = image(&server).download_with(&session, &path, &DownloadOptions::default()).await.unwrap();
+  assert_eq!(body.len() as u64, download.size);
+  assert_eq!(body.len() as u64, seen.0.load(Ordering::SeqCst));
+  assert_eq!(body, std::fs::read(&path).unwrap());
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn enforces_size_cap() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0u8; 2048]))
+    .mount(&server)
+    .await;
+
+  let path = common::temp_path("too-large.tif");
+  let mut options = DownloadOptions::default();
+  options.max_size(1024);
+  let e = image(&server).download_with(&Session::default(), &path, &options).await.unwrap_err();
+  assert!(matches!(e, Error::TooLarge { max_size: 1024, .. }));
+  assert!(!path.exists());
+  assert!(!path.with_file_name(format!(".{}.part", path.file_name().unwrap().to_string_lossy())).exists());
+}
+
+#[tokio::test]
+async fn failed_download_leaves_no_file() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(404))
+    .mount(&server)
+    .await;
+
+  let path = common::temp_path("missing.tif");
+  let e = image(&server).download(&Client::new(), &path).await.unwrap_err();
+  assert_eq!(Some(404), e.status_code());
+  assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn keeps_retry_after() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "7"))
+    .mount(&server)
+    .await;
+
+  let path = common::temp_path("busy.tif");
+  let e = image(&server).download(&Client::new(), &path).await.unwrap_err();
+  assert_eq!(Some(Duration::from_secs(7)), e.retry_after());
+}
+
+#[tokio::test]
+async fn rejects_html_error_pages() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw("<html>Gateway error</html>", "text/html"))
+    .mount(&server)
+    .await;
+
+  let path = common::temp_path("html.tif");
+  let e = image(&server).download(&Client::new(), &path).await.unwrap_err();
+  assert!(matches!(e, Error::FormatMismatch { expected: Format::Tif, found: None, .. }));
+  assert!(!path.exists());
+
+  let e = image(&server).request(&Client::new()).await.unwrap_err();
+  assert_eq!("text/html", match e {
+    Error::FormatMismatch { content_type, .. } => content_type.unwrap(),
+    e => panic!("expected a mismatch, got {}", e)
+  });
+}
+
+#[tokio::test]
+async fn detects_format() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(b"II\x2a\x00....".to_vec(), "image/tiff"))
+    .mount(&server)
+    .await;
+
+  let response = image(&server).request(&Client::new()).await.unwrap();
+  assert_eq!(Some(Format::Tif), response.format);
+}
+
+#[tokio::test]
+async fn verifies_short_and_empty_bodies() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/short/full/full/0/default.tif"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(b"II\x2a\x00".to_vec(), "application/octet-stream"))
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/gif/full/full/0/default.tif"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(b"GIF89a".to_vec(), "application/octet-stream"))
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/empty/full/full/0/default.tif"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(Vec::new(), "text/html"))
+    .mount(&server)
+    .await;
+
+  let path = common::temp_path("short.tif");
+  let mut api = image(&server);
+  api.identifier("short");
+  let download = api.download(&Client::new(), &path).await.unwrap();
+  assert_eq!(Some(Format::Tif), download.format);
+  assert_eq!(b"II\x2a\x00".to_vec(), std::fs::read(&path).unwrap());
+  std::fs::remove_file(&path).unwrap();
+
+  for identifier in ["gif", "empty"] {
+    let mut api = image(&server);
+    api.identifier(identifier);
+    let e = api.download(&Client::new(), &path).await.unwrap_err();
+    assert!(matches!(e, Error::FormatMismatch { expected: Format::Tif, .. }));
+    assert!(!path.exists());
+  }
+}
+
+#[tokio::test]
+async fn records_final_url() {
+  let server = MockServer::start().await;
+  let api = image(&server);
+  let requested = api.image_url().unwrap();
+  Mock::given(method("GET"))
+    .and(path(requested.path()))
+    .respond_with(ResponseTemplate::new(302).insert_header("Location", "/moved.tif"))
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/moved.tif"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(b"II\x2a\x00....".to_vec(), "image/tiff"))
+    .mount(&server)
+    .await;
+
+  let path = common::temp_path("download-redirect.tif");
+  let download = api.download(&Client::new(), &path).await.unwrap();
+  assert_eq!(requested, download.url);
+  assert_eq!("/moved.tif", download.final_url.path());
+  std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,55 @@
+use iiif::*;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const INFO: &str = include_str!("fixtures/info/cantaloupe_2.json");
+
+#[tokio::test]
+async fn keeps_headers_and_links() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc/full/full/0/default.jpg"))
+    .respond_with(ResponseTemplate::new(200)
+                    .insert_header("Content-Type", "image/jpeg")
+                    .insert_header("ETag", "\"v1\"")
+                    .insert_header("Access-Control-Allow-Origin", "*")
+                    .append_header("Link", "<http://iiif.io/api/image/2/level2.json>;rel=\"profile\"")
+                    .append_header("Link", "<https://example.org/iiif/abc/full/full/0/default.jpg>;rel=\"canonical\"")
+                    .set_body_bytes(vec![0xFF, 0xD8, 0xFF, 0xE0]))
+    .mount(&server)
+    .await;
+
+  let mut api = Image::new(&server.uri());
+  api.identifier("abc");
+  let response = api.request(&Client::new()).await.unwrap();
+  assert_eq!(Some("image/jpeg"), response.content_type());
+  assert_eq!(Some("\"v1\""), response.etag());
+  assert_eq!(None, response.last_modified());
+  assert_eq!(Some("*"), response.access_control_allow_origin());
+  assert_eq!(2, response.links().len());
+  assert_eq!(Some("http://iiif.io/api/image/2/level2.json".into()), response.profile_link());
+  assert_eq!(Some("https://example.org/iiif/abc/full/full/0/default.jpg".into()), response.canonical_link());
+  assert_eq!(response.url, response.final_url);
+}
+
+#[tokio::test]
+async fn follows_redirects() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/old/info.json"))
+    .respond_with(ResponseTemplate::new(301).insert_header("Location", "/new/info.json"))
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/new/info.json"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(INFO, "application/ld+json"))
+    .mount(&server)
+    .await;
+
+  let mut api = Image::new(&server.uri());
+  api.identifier("old");
+  let info = api.request_info(&Client::new()).await.unwrap();
+  assert_eq!("/old/info.json", info.url.path());
+  assert_eq!("/new/info.json", info.final_url.path());
+  assert_eq!(Some("application/ld+json"), info.content_type());
+}
@@ -0,0 +1,81 @@
+//! Deserializes info.json samples from common server implementations
+use iiif::*;
+
+fn fixture_json(name: &str) -> String {
+  let path = format!("{}/tests/fixtures/info/{}.json", env!("CARGO_MANIFEST_DIR"), name);
+  std::fs::read_to_string(&path).expect("Reading fixture")
+}
+
+fn fixture(name: &str) -> Info {
+  serde_json::from_str(&fixture_json(name)).unwrap_or_else(|e| panic!("Deserializing {}: {}", name, e))
+}
+
+#[test]
+fn iipimage() {
+  let info = fixture("iipimage");
+  assert_eq!(Version::V2, info.version());
+  assert_eq!(Some(ComplianceLevel::Level1), info.compliance_level());
+  assert_eq!((Some(5000), Some(5000)), (info.max_width(), info.max_height()));
+  assert!(info.supports_feature(&Feature::Mirroring));
+}
+
+#[test]
+fn cantaloupe() {
+  let v2 = fixture("cantaloupe_2");
+  let v3 = fixture("cantaloupe_3");
+  assert_eq!(Version::V2, v2.version());
+  assert_eq!(Version::V3, v3.version());
+  for info in [&v2, &v3] {
+    assert_eq!(Some(ComplianceLevel::Level2), info.compliance_level());
+    assert_eq!(Some(39052992), info.max_area());
+    assert!(info.supports_format(&Format::Tif));
+    assert!(info.supports_feature(&Feature::RotationArbitrary));
+  }
+  assert!(v3.supports_feature(&Feature::SizeUpscaling));
+}
+
+#[test]
+fn loris() {
+  let info = fixture("loris");
+  assert_eq!(0, info.tiles()[0].height);
+  assert!(info.supports_format(&Format::Webp));
+  assert!(info.supports_quality(&Quality::Bitonal));
+}
+
+#[test]
+fn level0_bare_string() {
+  let info = fixture("level0_string");
+  assert_eq!("http://iiif.io/api/image/2/level0.json", info.profile_uri());
+  assert_eq!(Some(ComplianceLevel::Level0), info.compliance_level());
+  assert!(info.formats().is_empty());
+  assert!(!info.supports_feature(&Feature::RegionByPx));
+}
+
+#[test]
+fn level0_uri_only_array() {
+  let info = fixture("level0_uri_array");
+  assert_eq!(Some(ComplianceLevel::Level0), info.compliance_level());
+  assert!(info.supports_format(&Format::Jpg));
+  assert!(!info.supports_format(&Format::Png));
+}
+
+#[test]
+fn several_profile_objects() {
+  let info = fixture("several_objects");
+  assert_eq!(vec!["png"], info.formats());
+  assert_eq!(vec!["mirroring", "rotationArbitrary"], info.supports());
+  assert_eq!(vec!["gray"], info.qualities());
+  assert_eq!(Some(3000), info.max_width());
+}
+
+#[test]
+fn reserializes_profile() {
+  for name in ["cantaloupe_2", "iipimage", "loris", "level0_uri_array", "several_objects"] {
+    let original: serde_json::Value = serde_json::from_str(&fixture_json(name)).unwrap();
+    assert_eq!(original, serde_json::to_value(fixture(name)).unwrap(), "Reserializing {}", name);
+  }
+  // A bare profile string is written back in the 2.1 array form
+  let mut original: serde_json::Value = serde_json::from_str(&fixture_json("level0_string")).unwrap();
+  original["profile"] = serde_json::json!([original["profile"]]);
+  assert_eq!(original, serde_json::to_value(fixture("level0_string")).unwrap());
+}
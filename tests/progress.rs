@@ -0,0 +1,116 @@
+use futures::stream::{self, StreamExt};
+use iiif::*;
+use std::sync::{Arc, Mutex};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Progress for Recorder {
+  fn started(&self, url: &Url, total: Option<u64>) {
+    self.push(format!("started {} {:?}", url.path(), total));
+  }
+
+  fn received(&self, url: &Url, received: u64, total: Option<u64>) {
+    self.push(format!("received {} {} {:?}", url.path(), received, total));
+  }
+
+  fn finished(&self, url: &Url, size: u64) {
+    self.push(format!("finished {} {}", url.path(), size));
+  }
+
+  fn failed(&self, url: &Url, error: &Error) {
+    self.push(format!("failed {} {:?}", url.path(), error.status_code()));
+  }
+}
+
+impl Recorder {
+  fn push(&self, event: String) {
+    self.0.lock().unwrap().push(event);
+  }
+
+  fn events(&self) -> Vec<String> {
+    self.0.lock().unwrap().clone()
+  }
+}
+
+fn session(recorder: &Recorder) -> Session {
+  let mut session = common::session();
+  session.progress(recorder.clone());
+  session
+}
+
+#[tokio::test]
+async fn reports_request_events() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc/full/full/0/default.jpg"))
+    .respond_with(ResponseTemplate::new(200).set_body_bytes(b"jpg".to_vec()))
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/missing/full/full/0/default.jpg"))
+    .respond_with(ResponseTemplate::new(404))
+    .mount(&server)
+    .await;
+
+  let recorder = Recorder::default();
+  let session = session(&recorder);
+  common::image(&server, "abc").request_with(&session).await.unwrap();
+  common::image(&server, "missing").request_with(&session).await.unwrap_err();
+  assert_eq!(vec![
+    "started /abc/full/full/0/default.jpg Some(3)",
+    "received /abc/full/full/0/default.jpg 3 Some(3)",
+    "finished /abc/full/full/0/default.jpg 3",
+    "failed /missing/full/full/0/default.jpg Some(404)"
+  ], recorder.events());
+}
+
+#[tokio::test]
+async fn reports_batch_and_streaming_downloads() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![1u8; 100]))
+    .mount(&server)
+    .await;
+
+  let recorder = Recorder::default();
+  let batch = BatchDownloader::with_session(&session(&recorder));
+  let images = stream::iter(vec![common::image(&server, "a"), common::image(&server, "b"), common::image(&server, "c")]);
+  assert_eq!(3, batch.download(images).count().await);
+  let events = recorder.events();
+  assert_eq!(3, events.iter().filter(|e| e.starts_with("finished") && e.ends_with(" 100")).count());
+
+  let recorder = Recorder::default();
+  let path = common::temp_path("progress.jpg");
+  common::image(&server, "d").download_with(&session(&recorder), &path, &DownloadOptions::default()).await.unwrap();
+  let events = recorder.events();
+  assert_eq!("started /d/full/full/0/default.jpg Some(100)", events[0]);
+  assert_eq!("finished /d/full/full/0/default.jpg 100", events[events.len() - 1]);
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn failed_batch_write_is_not_finished() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(200).set_body_bytes(b"jpg".to_vec()))
+    .mount(&server)
+    .await;
+
+  // A file where the template expects a directory makes every write fail
+  let blocker = common::temp_path("blocker");
+  std::fs::write(&blocker, b"").unwrap();
+  let recorder = Recorder::default();
+  let mut batch = BatchDownloader::with_session(&session(&recorder));
+  batch.path_template(&format!("{}/{{identifier}}.jpg", blocker.display()));
+  let results: Vec<_> = batch.download(stream::iter(vec![common::image(&server, "a")])).collect().await;
+  assert!(matches!(results[0].1, Err(Error::Io(_))));
+  let events = recorder.events();
+  assert_eq!("failed /a/full/full/0/default.jpg None", events[events.len() - 1]);
+  assert!(!events.iter().any(|e| e.starts_with("finished")));
+  std::fs::remove_file(&blocker).unwrap();
+}
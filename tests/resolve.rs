@@ -0,0 +1,91 @@
+use iiif::*;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn info(id: &str) -> String {
+  format!(r#"{{
+    "@context": "http://iiif.io/api/image/3/context.json",
+    "id": "{}",
+    "type": "ImageService3",
+    "protocol": "http://iiif.io/api/image",
+    "profile": "level1",
+    "width": 1000,
+    "height": 800
+  }}"#, id)
+}
+
+#[tokio::test]
+async fn follows_base_uri_redirect() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/iiif/abc"))
+    .respond_with(ResponseTemplate::new(303).insert_header("Location", "/iiif/abc/info.json"))
+    .expect(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/iiif/abc/info.json"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(info(&format!("{}/proxy/iiif/abc", server.uri())), "application/ld+json"))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let mut api = Image::new(&format!("{}/iiif", server.uri()));
+  api.identifier("abc");
+  api.width(500);
+  let info = api.resolve(&Session::default()).await.unwrap();
+  assert_eq!(1000, info.width());
+  assert_eq!(server.uri(), api.host);
+  assert_eq!(vec!["proxy", "iiif"], api.prefixes);
+  assert_eq!(Version::V3, api.version);
+  assert_eq!(Size::W(500), api.size);
+  assert_eq!(format!("{}/proxy/iiif/abc/full/500,/0/default.jpg", server.uri()), api.image_url().unwrap().as_str());
+}
+
+#[tokio::test]
+async fn falls_back_to_info_json() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc"))
+    .respond_with(ResponseTemplate::new(404))
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/abc/info.json"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(info("https://images.example.org/iiif/abc"), "application/json"))
+    .mount(&server)
+    .await;
+
+  let mut api = Image::new(&server.uri());
+  api.identifier("abc");
+  api.resolve(&Session::default()).await.unwrap();
+  assert_eq!("https://images.example.org/iiif/abc/info.json", api.info_url().unwrap().as_str());
+}
+
+#[tokio::test]
+async fn follows_v2_id_to_another_host() {
+  let server = MockServer::start().await;
+  let info = r#"{
+    "@context": "http://iiif.io/api/image/2/context.json",
+    "@id": "http://images.example.org:8080/loris/abc",
+    "protocol": "http://iiif.io/api/image",
+    "profile": ["http://iiif.io/api/image/2/level2.json"],
+    "width": 1000,
+    "height": 800
+  }"#;
+  Mock::given(method("GET"))
+    .and(path("/abc"))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(info, "application/json"))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let mut api = Image::new(&server.uri());
+  api.identifier("abc");
+  api.version(Version::V3);
+  api.resolve(&Session::default()).await.unwrap();
+  assert_eq!("http://images.example.org:8080", api.host);
+  assert_eq!(vec!["loris"], api.prefixes);
+  assert_eq!(Version::V2, api.version);
+  assert_eq!("http://images.example.org:8080/loris/abc/full/full/0/default.jpg", api.image_url().unwrap().as_str());
+}
@@ -0,0 +1,116 @@
+use iiif::*;
+use std::time::{Duration, Instant};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+fn session(max_attempts: u32) -> Session {
+  let mut retry = RetryPolicy::default();
+  retry.max_attempts(max_attempts);
+  retry.delays(Duration::from_millis(10), Duration::from_millis(50));
+  let mut session = common::session();
+  session.retry(retry);
+  session
+}
+
+#[tokio::test]
+async fn retries_until_success() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc/full/full/0/default.jpg"))
+    .respond_with(ResponseTemplate::new(503))
+    .up_to_n_times(2)
+    .expect(2)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/abc/full/full/0/default.jpg"))
+    .respond_with(ResponseTemplate::new(200).set_body_bytes(b"jpg".to_vec()))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let response = common::image(&server, "abc").request_with(&session(3)).await.unwrap();
+  assert_eq!(200, response.status_code);
+  assert_eq!(&b"jpg"[..], &response.image[..]);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(502))
+    .expect(3)
+    .mount(&server)
+    .await;
+
+  let e = common::image(&server, "abc").request_info_with(&session(3)).await.unwrap_err();
+  assert_eq!(Some(502), e.status_code());
+}
+
+#[tokio::test]
+async fn does_not_retry_client_errors() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(404))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let e = common::image(&server, "abc").request_with(&session(5)).await.unwrap_err();
+  assert_eq!(Some(404), e.status_code());
+}
+
+#[tokio::test]
+async fn plain_request_does_not_retry() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(500))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  assert!(common::image(&server, "abc").request(&Client::new()).await.is_err());
+}
+
+#[tokio::test]
+async fn honours_retry_after() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .and(path("/abc/info.json"))
+    .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+    .up_to_n_times(1)
+    .mount(&server)
+    .await;
+  Mock::given(method("GET"))
+    .and(path("/abc/info.json"))
+    .respond_with(ResponseTemplate::new(200).set_body_string(include_str!("fixtures/info/cantaloupe_3.json")))
+    .mount(&server)
+    .await;
+
+  let mut retry = RetryPolicy::default();
+  retry.max_attempts(2);
+  retry.delays(Duration::from_millis(10), Duration::from_secs(2));
+  let mut session = Session::new(&Client::new());
+  session.retry(retry);
+  let start = Instant::now();
+  let info = common::image(&server, "abc").request_info_with(&session).await.unwrap();
+  assert!(start.elapsed() >= Duration::from_secs(1));
+  assert_eq!(Version::V3, info.version());
+}
+
+#[tokio::test]
+async fn gives_up_on_retry_after_above_max_delay() {
+  let server = MockServer::start().await;
+  Mock::given(method("GET"))
+    .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "3600"))
+    .expect(1)
+    .mount(&server)
+    .await;
+
+  let start = Instant::now();
+  let e = common::image(&server, "abc").request_info_with(&session(3)).await.unwrap_err();
+  assert!(start.elapsed() < Duration::from_secs(1));
+  assert_eq!(Some(Duration::from_secs(3600)), e.retry_after());
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "stitch")]
+use image::{ImageFormat, Rgb, RgbImage};
+use iiif::*;
+use std::io::Cursor;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+mod common;
+
+const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]];
+
+fn info() -> Info {
+  Info::V2(InfoV2{
+    width: 512,
+    height: 400,
+    tiles: vec![Tile{ width: 256, height: 0, scale_factors: vec![1], ..Default::default() }],
+    ..Default::default()
+  })
+}
+
+// Serves each tile of the 2x2 grid as a png of its own color
+async fn tile_server() -> (MockServer, Image) {
+  let server = MockServer::start().await;
+  let mut api = common::image(&server, "abc");
+  api.png();
+  for (tile, color) in api.tiles(&info(), 1).unwrap().zip(COLORS) {
+    let (w, h) = match &tile.region {
+      Region::Abs(region) => (region.w, region.h),
+      _ => unreachable!()
+    };
+    let mut body = Vec::new();
+    RgbImage::from_pixel(w as u32, h as u32, Rgb(color))
+      .write_to(&mut Cursor::new(&mut body), ImageFormat::Png)
+      .unwrap();
+    Mock::given(method("GET"))
+      .and(path(tile.image_url().unwrap().path()))
+      .respond_with(ResponseTemplate::new(200).set_body_raw(body, "image/png"))
+      .expect(1)
+      .mount(&server)
+      .await;
+  }
+  (server, api)
+}
+
+fn assert_grid(image: &RgbImage) {
+  assert_eq!((512, 400), image.dimensions());
+  assert_eq!(&Rgb(COLORS[0]), image.get_pixel(0, 0));
+  assert_eq!(&Rgb(COLORS[1]), image.get_pixel(511, 255));
+  assert_eq!(&Rgb(COLORS[2]), image.get_pixel(255, 256));
+  assert_eq!(&Rgb(COLORS[3]), image.get_pixel(256, 399));
+}
+
+#[tokio::test]
+async fn stitches_tile_grid() {
+  let (_server, api) = tile_server().await;
+  let image = api.stitch(&Client::new(), &info(), 1, 2).await.unwrap();
+  assert_grid(&image.to_rgb8());
+}
+
+#[tokio::test]
+async fn stitches_tile_grid_to_png() {
+  let (_server, api) = tile_server().await;
+  let path = common::temp_path("stitch.png");
+  api.stitch_to_png(&Client::new(), &info(), 1, 2, path.to_str().unwrap()).await.unwrap();
+  assert_grid(&image::open(&path).unwrap().to_rgb8());
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn failed_tile_leaves_no_png() {
+  // Only the first tile is served, the rest are not found
+  let server = MockServer::start().await;
+  let mut api = common::image(&server, "abc");
+  api.png();
+  let mut body = Vec::new();
+  RgbImage::from_pixel(256, 256, Rgb(COLORS[0]))
+    .write_to(&mut Cursor::new(&mut body), ImageFormat::Png)
+    .unwrap();
+  let first = api.tiles(&info(), 1).unwrap().next().unwrap();
+  Mock::given(method("GET"))
+    .and(path(first.image_url().unwrap().path()))
+    .respond_with(ResponseTemplate::new(200).set_body_raw(body, "image/png"))
+    .mount(&server)
+    .await;
+  let dir = common::temp_path("stitch-failed");
+  std::fs::create_dir_all(&dir).unwrap();
+  let path = dir.join("image.png");
+  assert!(api.stitch_to_png(&Client::new(), &info(), 1, 2, path.to_str().unwrap()).await.is_err());
+  assert_eq!(0, std::fs::read_dir(&dir).unwrap().count());
+  std::fs::remove_dir(&dir).unwrap();
+}